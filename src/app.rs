@@ -1,15 +1,27 @@
 use crate::models::*;
 use crate::download::*;
-use crate::server::{find_server_bin, guess_backend, pick_asset_url, want_asset_name};
+use crate::server::{find_server_bin, guess_backend};
 use crate::scan::*;
-use crate::runtime::*;
+use crate::runtime::{parse_runtime_dir, tag_number, LEGACY_BIN_DIR, RUNTIMES_DIR};
 use std::{
+    collections::HashMap,
     fs,
     path::PathBuf,
-    sync::mpsc,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+        mpsc,
+    },
     time::{Duration, Instant},
 };
 
+/// Wait between automatic update checks that failed; unauthenticated
+/// GitHub API calls are limited to 60 an hour
+const UPDATE_RETRY_AFTER: Duration = Duration::from_secs(15 * 60);
+/// How long a dead server's output may keep arriving before its exit is
+/// reported without it
+const LOG_DRAIN_GRACE: Duration = Duration::from_secs(2);
+
 pub struct App {
     pub backend: Backend,
     pub status: String,
@@ -18,22 +30,43 @@ pub struct App {
     pub server_bin: Option<PathBuf>,
     pub server_child: Option<std::process::Child>,
     pub server_url: String,
+    pub server_port: Option<u16>,
+    pub server_alive: Arc<AtomicBool>,
+    pub server_fault: Option<ServerFault>,
+    pub restart_attempts: u32,
+    pub restart_at: Option<Instant>,
+    /// Exit of a server whose log is still being read, with the deadline
+    /// for reporting it anyway
+    pub server_exited: Option<(std::process::ExitStatus, Instant)>,
+    /// Old server shutting down during a model switch, with its kill deadline
+    pub stopping_child: Option<(std::process::Child, Instant)>,
+    pub pending_switch: bool,
     pub model_repo: String,
     pub model_file: String,
     pub model_path: Option<PathBuf>,
     pub msgs: Vec<Msg>,
+    // Every branch of the open chat; `path` holds the node of each message
+    // in `msgs` that is already stored there
+    pub tree: crate::tree::ChatTree,
+    pub path: Vec<usize>,
     pub input: String,
     pub editing: Option<usize>,
+    pub edit_buffer: String,
     pub rx: Option<mpsc::Receiver<StreamEvent>>,
-    pub dl_rx: Option<mpsc::Receiver<DownloadEvent>>,
-    pub runtime_progress: Option<(u64, Option<u64>, String)>,
-    pub model_progress: Option<(u64, Option<u64>, String)>,
+    pub stream_cancel: Option<crate::stream::CancelHandle>,
+    pub downloads: crate::download_manager::DownloadManager,
+    pub show_downloads: bool,
     pub search_query: String,
     pub search_results: Vec<String>,
     pub selected_model: Option<String>,
     pub files_for_selected: Vec<HFFile>,
     pub search_status: String,
     pub server_log: Vec<String>,
+    /// Lines ever added to `server_log`, so views notice when old ones drop out
+    pub server_log_seq: u64,
+    pub log_file: crate::logs::LogWriter,
+    pub show_logs: bool,
+    pub log_view: crate::ui_logs::LogView,
     pub log_rx: Option<mpsc::Receiver<String>>,
     pub server_ready: bool,
     pub loaded_model: Option<String>,
@@ -47,6 +80,78 @@ pub struct App {
     pub server_status: ServerStatus,
     pub last_activity: Instant,
     pub show_settings: bool,
+
+    // Conversation history
+    pub history_dir: PathBuf,
+    pub conversations: Vec<Conversation>,
+    pub current_conversation: Option<String>,
+    pub renaming: Option<(String, String)>,
+
+    // Sampling preset used for the current conversation
+    pub current_preset: String,
+    pub current_persona: Option<String>,
+
+    // Launch profile, sampling preset and persona shown in the settings editors
+    pub editing_profile: String,
+    pub editing_preset: String,
+    pub editing_persona: String,
+    // Settings name field being typed into, keyed by field, while its text
+    // is not a usable name yet
+    pub name_draft: Option<(String, String)>,
+
+    // Downloaded model whose GGUF details are expanded
+    pub info_model: Option<PathBuf>,
+
+    // Memory available for models, and GGUF headers fetched from HF by
+    // file; `None` while the fetch is running
+    pub system_memory: SystemMemory,
+    pub memory_checked_at: Instant,
+    pub hf_headers: HashMap<String, Option<Result<Arc<GgufInfo>, String>>>,
+    pub hf_header_tx: mpsc::Sender<crate::hf::HeaderFetch>,
+    pub hf_header_rx: mpsc::Receiver<crate::hf::HeaderFetch>,
+
+    // llama.cpp release check
+    pub update_rx: Option<mpsc::Receiver<Result<GhRelease, String>>>,
+    // Last automatic update check, successful or not, to pace retries
+    pub update_attempted_at: Option<Instant>,
+    pub latest_release: Option<GhRelease>,
+    pub show_release_notes: bool,
+    pub update_dismissed: bool,
+
+    // Path typed into the "add runtime" field in settings
+    pub custom_runtime_input: String,
+
+    // Connection used by the current conversation and probe results by name
+    pub current_connection: String,
+    pub editing_connection: String,
+    pub connection_health: HashMap<String, ConnectionHealth>,
+    pub health_tx: mpsc::Sender<(String, Result<Vec<ModelEntry>, String>)>,
+    pub health_rx: mpsc::Receiver<(String, Result<Vec<ModelEntry>, String>)>,
+
+    // `--version` results for runtimes found outside the app's folder
+    pub probe_tx: mpsc::Sender<crate::discover::VersionProbe>,
+    pub probe_rx: mpsc::Receiver<crate::discover::VersionProbe>,
+    pub probes_pending: usize,
+    // Models each connection listed on its last successful check
+    pub connection_models: HashMap<String, Vec<ModelEntry>>,
+    pub models_checked_at: Instant,
+
+    // Parsed assistant replies by message index
+    pub markdown: crate::markdown::MarkdownCache,
+    // Measured chat message heights for the virtualized list
+    pub msg_heights: crate::ui_chat::MessageHeights,
+    // Jump to the newest message on the next frame
+    pub scroll_to_bottom: bool,
+
+    // Performance dashboard
+    pub show_perf: bool,
+    pub perf: crate::metrics::PerfHistory,
+    pub perf_url: String,
+    pub perf_client: reqwest::blocking::Client,
+    pub perf_tx: mpsc::Sender<crate::metrics::ServerSample>,
+    pub perf_rx: mpsc::Receiver<crate::metrics::ServerSample>,
+    pub perf_polled_at: Instant,
+    pub perf_in_flight: bool,
 }
 
 impl Default for App {
@@ -67,6 +172,21 @@ impl Default for App {
             Settings::default()
         };
 
+        let history_dir = dir.data_dir().join("conversations");
+        let _ = fs::create_dir_all(&history_dir);
+        let mut conversations = crate::history::load_conversations(&history_dir);
+        crate::history::enforce_history_limit(&history_dir, &mut conversations, settings.max_chat_history);
+
+        let server_url = crate::server::server_url_for(match settings.server_port {
+            0 => 8080,
+            p => p,
+        });
+
+        let (health_tx, health_rx) = mpsc::channel();
+        let (probe_tx, probe_rx) = mpsc::channel();
+        let (perf_tx, perf_rx) = mpsc::channel();
+        let (hf_header_tx, hf_header_rx) = mpsc::channel();
+
         let mut app = Self {
             backend: Backend::Auto,
             status: "Initializing...".into(),
@@ -74,23 +194,38 @@ impl Default for App {
             model_dir,
             server_bin: None,
             server_child: None,
-            server_url: "http://127.0.0.1:8080".into(),
+            server_url,
+            server_port: None,
+            server_alive: Arc::new(AtomicBool::new(false)),
+            server_fault: None,
+            restart_attempts: 0,
+            restart_at: None,
+            server_exited: None,
+            stopping_child: None,
+            pending_switch: false,
             model_repo: "TheBloke/Mistral-7B-Instruct-v0.2-GGUF".into(),
             model_file: "mistral-7b-instruct-v0.2.Q4_K_M.gguf".into(),
             model_path: None,
             msgs: vec![],
+            tree: Default::default(),
+            path: vec![],
             input: String::new(),
             editing: None,
+            edit_buffer: String::new(),
             rx: None,
-            dl_rx: None,
-            runtime_progress: None,
-            model_progress: None,
+            stream_cancel: None,
+            downloads: Default::default(),
+            show_downloads: false,
             search_query: String::new(),
             search_results: vec![],
             selected_model: None,
             files_for_selected: vec![],
             search_status: String::new(),
             server_log: Vec::new(),
+            server_log_seq: 0,
+            log_file: crate::logs::LogWriter::new(dir.data_dir().join(crate::logs::LOGS_DIR)),
+            show_logs: false,
+            log_view: Default::default(),
             log_rx: None,
             server_ready: false,
             loaded_model: None,
@@ -104,7 +239,66 @@ impl Default for App {
             server_status: ServerStatus::Stopped,
             last_activity: Instant::now(),
             show_settings: false,
+
+            history_dir,
+            conversations,
+            current_conversation: None,
+            renaming: None,
+
+            current_preset: String::new(),
+            current_persona: None,
+
+            editing_profile: String::new(),
+            editing_preset: String::new(),
+            editing_persona: String::new(),
+            name_draft: None,
+
+            info_model: None,
+
+            system_memory: crate::fit::detect_system_memory(guess_backend()),
+            memory_checked_at: Instant::now(),
+            hf_headers: HashMap::new(),
+            hf_header_tx,
+            hf_header_rx,
+
+            update_rx: None,
+            update_attempted_at: None,
+            latest_release: None,
+            show_release_notes: false,
+            update_dismissed: false,
+
+            custom_runtime_input: String::new(),
+
+            current_connection: String::new(),
+            editing_connection: String::new(),
+            connection_health: HashMap::new(),
+            health_tx,
+            health_rx,
+            probe_tx,
+            probe_rx,
+            probes_pending: 0,
+            connection_models: HashMap::new(),
+            models_checked_at: Instant::now(),
+
+            markdown: Default::default(),
+            msg_heights: Default::default(),
+            scroll_to_bottom: false,
+
+            show_perf: false,
+            perf: Default::default(),
+            perf_url: String::new(),
+            perf_client: reqwest::blocking::Client::new(),
+            perf_tx,
+            perf_rx,
+            perf_polled_at: Instant::now(),
+            perf_in_flight: false,
         };
+        app.editing_profile = app.settings.default_launch_profile.clone();
+        app.current_connection = app.settings.default_connection.clone();
+        app.editing_connection = app.settings.default_connection.clone();
+        app.current_preset = app.settings.default_preset.clone();
+        app.editing_preset = app.settings.default_preset.clone();
+        app.select_persona(app.settings.default_persona.clone());
 
         // Auto-detect runtimes
         app.detect_runtimes();
@@ -139,22 +333,202 @@ impl Default for App {
 }
 
 impl App {
-    /// Detect available runtimes in the runtime directory
+    /// Detect available runtimes in the runtime directory, newest first
     pub fn detect_runtimes(&mut self) {
         self.available_runtimes.clear();
-        let bin_dir = self.runtime_dir.join("llama-bin");
-
-        if bin_dir.exists() {
-            if let Some(server_bin) = find_server_bin(&bin_dir) {
-                let runtime = RuntimeInfo {
-                    name: "Local Runtime".to_string(),
-                    path: server_bin,
-                    version: "Unknown".to_string(),
-                    backend: Backend::Auto,
+        if let Ok(read) = fs::read_dir(self.runtime_dir.join(RUNTIMES_DIR)) {
+            for dir in read.flatten().map(|e| e.path()).filter(|p| p.is_dir()) {
+                let Some(name) = dir.file_name().map(|s| s.to_string_lossy().to_string()) else {
+                    continue;
+                };
+                let (Some((tag, backend)), Some(server_bin)) = (parse_runtime_dir(&name), find_server_bin(&dir)) else {
+                    continue;
                 };
+                self.available_runtimes.push(RuntimeInfo {
+                    name,
+                    path: server_bin,
+                    version: tag,
+                    backend,
+                    dir,
+                    source: RuntimeSource::Managed,
+                    build_info: String::new(),
+                });
+            }
+        }
+        self.available_runtimes
+            .sort_by_key(|r| std::cmp::Reverse((tag_number(&r.version), r.name.clone())));
+
+        let bin_dir = self.runtime_dir.join(LEGACY_BIN_DIR);
+        if let Some(server_bin) = find_server_bin(&bin_dir) {
+            self.available_runtimes.push(RuntimeInfo {
+                name: "Local Runtime".to_string(),
+                path: server_bin,
+                version: "Unknown".to_string(),
+                backend: Backend::Auto,
+                dir: bin_dir,
+                source: RuntimeSource::Managed,
+                build_info: String::new(),
+            });
+        }
+
+        let mut probe = Vec::new();
+        for runtime in crate::discover::discover_system_runtimes(&self.settings.custom_runtime_paths) {
+            let same = |r: &RuntimeInfo| r.path.canonicalize().ok() == runtime.path.canonicalize().ok();
+            if !self.available_runtimes.iter().any(same) {
+                probe.push(runtime.path.clone());
                 self.available_runtimes.push(runtime);
             }
         }
+        self.probes_pending += probe.len();
+        crate::discover::spawn_version_probes(probe, self.probe_tx.clone());
+    }
+
+    /// Fill in versions reported by `--version` probes; true if any arrived
+    pub fn poll_runtime_probes(&mut self) -> bool {
+        let mut got = false;
+        while let Ok((bin, result)) = self.probe_rx.try_recv() {
+            self.probes_pending = self.probes_pending.saturating_sub(1);
+            got = true;
+            let Some((version, build_info, backend)) = result else {
+                continue;
+            };
+            let runtimes = self.available_runtimes.iter_mut().chain(self.current_runtime.as_mut());
+            for r in runtimes.filter(|r| r.path == bin) {
+                r.version = version.clone();
+                r.build_info = build_info.clone();
+                r.backend = backend;
+            }
+        }
+        got
+    }
+
+    /// Use a detected runtime for the next server start and remember it
+    pub fn select_runtime(&mut self, name: &str) {
+        let Some(runtime) = self.available_runtimes.iter().find(|r| r.name == name).cloned() else {
+            return;
+        };
+        self.server_bin = Some(runtime.path.clone());
+        self.settings.default_runtime = Some(runtime.name.clone());
+        let _ = self.save_settings();
+        self.status = if self.server_child.is_some() {
+            format!("Runtime selected: {} (restart the server to use it)", runtime.name)
+        } else {
+            format!("Runtime selected: {}", runtime.name)
+        };
+        self.current_runtime = Some(runtime);
+    }
+
+    /// Next older installed build of the current runtime's backend
+    pub fn previous_runtime(&self) -> Option<&RuntimeInfo> {
+        let current = self.current_runtime.as_ref()?;
+        let number = tag_number(&current.version)?;
+        self.available_runtimes
+            .iter()
+            .filter(|r| r.backend == current.backend)
+            .filter(|r| tag_number(&r.version).is_some_and(|n| n < number))
+            .max_by_key(|r| tag_number(&r.version))
+    }
+
+    /// Poll a running release check, or start one when `force` is set or
+    /// the configured interval has passed
+    pub fn check_runtime_updates(&mut self, force: bool) {
+        if force && self.update_rx.is_some() {
+            return;
+        }
+        if let Some(rx) = &self.update_rx {
+            match rx.try_recv() {
+                Ok(Ok(rel)) => {
+                    self.update_rx = None;
+                    self.settings.last_update_check = crate::history::now_secs();
+                    let _ = self.save_settings();
+                    self.latest_release = Some(rel);
+                    self.update_dismissed = false;
+                }
+                Ok(Err(e)) => {
+                    self.update_rx = None;
+                    self.status = format!("Update check failed: {e}");
+                }
+                Err(mpsc::TryRecvError::Empty) => {}
+                Err(mpsc::TryRecvError::Disconnected) => self.update_rx = None,
+            }
+            return;
+        }
+        let hours = self.settings.update_check_hours as u64;
+        // A failed check does not move `last_update_check`, so wait a while
+        // before trying again instead of hitting GitHub every frame
+        let due = hours > 0
+            && crate::history::now_secs().saturating_sub(self.settings.last_update_check) >= hours * 3600
+            && self
+                .update_attempted_at
+                .is_none_or(|t| t.elapsed() >= UPDATE_RETRY_AFTER);
+        if !(force || due) {
+            return;
+        }
+        self.update_attempted_at = Some(Instant::now());
+        let (tx, rx) = mpsc::channel();
+        self.update_rx = Some(rx);
+        std::thread::spawn(move || {
+            let _ = tx.send(crate::runtime::fetch_latest_release().map_err(|e| e.to_string()));
+        });
+    }
+
+    /// Backend that runtime updates are looked up for
+    pub fn update_backend(&self) -> Backend {
+        match &self.current_runtime {
+            Some(r) if r.backend != Backend::Auto => r.backend,
+            _ => self.effective_backend(),
+        }
+    }
+
+    /// Latest release, if it is newer than every installed build for the
+    /// backend in use and has an asset for it
+    pub fn runtime_update(&self) -> Option<&GhRelease> {
+        let rel = self.latest_release.as_ref()?;
+        let be = self.update_backend();
+        let latest = tag_number(&rel.tag_name)?;
+        let newest_installed = self
+            .available_runtimes
+            .iter()
+            .filter(|r| r.backend == be)
+            .filter_map(|r| tag_number(&r.version))
+            .max();
+        if newest_installed.is_some_and(|n| n >= latest) {
+            return None;
+        }
+        crate::server::pick_asset_url(rel, crate::server::want_asset_name(be))?;
+        let dir = crate::runtime::runtime_install_dir(&self.runtime_dir, &rel.tag_name, be);
+        if self.downloads.find_active(&dir).is_some() {
+            return None;
+        }
+        Some(rel)
+    }
+
+    /// Remove an installed runtime from disk; the selected one falls back to
+    /// the newest remaining build
+    pub fn delete_runtime(&mut self, name: &str) -> anyhow::Result<()> {
+        let Some(runtime) = self.available_runtimes.iter().find(|r| r.name == name).cloned() else {
+            return Ok(());
+        };
+        if runtime.source != RuntimeSource::Managed {
+            anyhow::bail!("{name} was not installed by this app");
+        }
+        if self.server_child.is_some() && self.server_bin.as_ref() == Some(&runtime.path) {
+            anyhow::bail!("{name} is in use by the running server");
+        }
+        fs::remove_dir_all(&runtime.dir)?;
+        self.detect_runtimes();
+        if self.current_runtime.as_ref().is_some_and(|r| r.name == name) {
+            self.current_runtime = None;
+            self.server_bin = None;
+            self.settings.default_runtime = None;
+            self.settings.runtime_pinned = false;
+            let _ = self.save_settings();
+            if let Some(newest) = self.available_runtimes.first().map(|r| r.name.clone()) {
+                self.select_runtime(&newest);
+            }
+        }
+        self.status = format!("Deleted runtime {name}");
+        Ok(())
     }
 
     /// Save current settings to disk
@@ -168,16 +542,209 @@ impl App {
 
     /// Auto-start server if needed
     pub fn ensure_server_running(&mut self) {
+        if !self.using_local_server() {
+            return;
+        }
         if !self.server_ready && matches!(self.server_status, ServerStatus::Stopped) && self.settings.auto_start_server {
             if self.server_child.is_none() {
-                self.server_status = ServerStatus::Starting;
                 self.status = "Auto-starting server...".into();
-                if let Err(e) = crate::server::start_server(self) {
-                    self.server_status = ServerStatus::Error(format!("Auto-start failed: {e}"));
-                    self.status = format!("Auto-start err: {e}");
+                self.launch_server();
+            }
+        }
+    }
+
+    /// Start llama-server now, recording any failure in `server_status`
+    pub fn launch_server(&mut self) {
+        self.server_status = ServerStatus::Starting;
+        if let Err(e) = crate::server::start_server(self) {
+            self.server_status = ServerStatus::Error(format!("Start failed: {e}"));
+            self.status = format!("Server err: {e}");
+        }
+    }
+
+    /// Connection of the current conversation, falling back to the default
+    /// one and then to the managed local server
+    pub fn active_connection(&self) -> Connection {
+        let list = &self.settings.connections;
+        list.iter()
+            .find(|c| c.name == self.current_connection)
+            .or_else(|| list.iter().find(|c| c.name == self.settings.default_connection))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn using_local_server(&self) -> bool {
+        self.active_connection().kind == ConnectionKind::LocalManaged
+    }
+
+    /// Base URL and API key for chat requests
+    pub fn chat_endpoint(&self) -> (String, Option<String>) {
+        crate::connection::endpoint(&self.active_connection(), &self.server_url)
+    }
+
+    pub fn select_connection(&mut self, name: &str) {
+        if !self.settings.connections.iter().any(|c| c.name == name) {
+            return;
+        }
+        self.current_connection = name.to_string();
+        if !self.using_local_server() && !self.connection_health.contains_key(name) {
+            self.check_connection(name);
+        }
+    }
+
+    /// Probe `/v1/models` of a connection in the background
+    pub fn check_connection(&mut self, name: &str) {
+        let Some(conn) = self.settings.connections.iter().find(|c| c.name == name) else {
+            return;
+        };
+        let (url, key) = crate::connection::endpoint(conn, &self.server_url);
+        self.connection_health.insert(name.to_string(), ConnectionHealth::Checking);
+        crate::connection::spawn_health_check(name.to_string(), url, key, self.health_tx.clone());
+    }
+
+    pub fn poll_connection_health(&mut self) -> bool {
+        let mut got = false;
+        while let Ok((name, result)) = self.health_rx.try_recv() {
+            let health = match result {
+                Ok(models) => {
+                    let n = models.len();
+                    self.keep_model_choice(&name, &models);
+                    self.connection_models.insert(name.clone(), models);
+                    ConnectionHealth::Online(n)
                 }
+                Err(e) => ConnectionHealth::Offline(e),
+            };
+            self.connection_health.insert(name, health);
+            got = true;
+        }
+        got
+    }
+
+    /// Keep a connection's chosen model if the server still lists it,
+    /// otherwise fall back to the first listed one
+    fn keep_model_choice(&mut self, name: &str, models: &[ModelEntry]) {
+        let Some(conn) = self.settings.connections.iter_mut().find(|c| c.name == name) else {
+            return;
+        };
+        if models.iter().any(|m| m.id == conn.model) {
+            return;
+        }
+        let fallback = models.first().map(|m| m.id.clone()).unwrap_or_default();
+        if conn.model != fallback {
+            conn.model = fallback;
+            let _ = self.save_settings();
+        }
+    }
+
+    /// Re-read free RAM every few seconds; it changes as other programs run
+    pub fn refresh_memory_if_due(&mut self) {
+        if self.memory_checked_at.elapsed() < Duration::from_secs(5) {
+            return;
+        }
+        self.memory_checked_at = Instant::now();
+        self.system_memory.ram_available = crate::fit::available_ram();
+    }
+
+    /// Start reading the GGUF header of a file in the open HF repo
+    pub fn fetch_hf_header(&mut self, repo: &str, file: &str) {
+        self.hf_headers.insert(file.to_string(), None);
+        crate::hf::spawn_gguf_info(repo.to_string(), file.to_string(), self.hf_header_tx.clone());
+    }
+
+    /// Store finished header reads; ones for a repo no longer open are dropped
+    pub fn poll_hf_headers(&mut self) -> bool {
+        let mut got = false;
+        while let Ok((repo, file, result)) = self.hf_header_rx.try_recv() {
+            if self.selected_model.as_deref() == Some(repo.as_str()) && self.hf_headers.contains_key(&file) {
+                self.hf_headers.insert(file, Some(result.map(Arc::new)));
+                got = true;
             }
         }
+        got
+    }
+
+    /// Re-read the model list of the current connection once a minute
+    pub fn refresh_models_if_due(&mut self) {
+        if self.models_checked_at.elapsed() < Duration::from_secs(60) {
+            return;
+        }
+        self.models_checked_at = Instant::now();
+        if self.using_local_server() && !self.server_ready {
+            return;
+        }
+        let name = self.active_connection().name;
+        if self.connection_health.get(&name) != Some(&ConnectionHealth::Checking) {
+            self.check_connection(&name);
+        }
+    }
+
+    /// llama-server whose monitoring endpoints the dashboard charts: the
+    /// managed one once it is ready, or a remote llama-server connection
+    pub fn perf_endpoint(&self) -> Option<(String, Option<String>)> {
+        match self.active_connection().kind {
+            ConnectionKind::LocalManaged if self.server_ready => Some(self.chat_endpoint()),
+            ConnectionKind::LlamaServer => Some(self.chat_endpoint()),
+            _ => None,
+        }
+    }
+
+    /// Collect finished samples and start the next one about once a second
+    /// while the dashboard is open
+    pub fn poll_perf(&mut self) -> bool {
+        let mut got = false;
+        while let Ok(sample) = self.perf_rx.try_recv() {
+            self.perf.push(sample);
+            self.perf_in_flight = false;
+            got = true;
+        }
+        if !self.show_perf || self.perf_in_flight || self.perf_polled_at.elapsed() < Duration::from_secs(1) {
+            return got;
+        }
+        let Some((url, key)) = self.perf_endpoint() else {
+            return got;
+        };
+        if url != self.perf_url {
+            self.perf.clear();
+            self.perf_url = url.clone();
+        }
+        self.perf_in_flight = true;
+        self.perf_polled_at = Instant::now();
+        crate::metrics::spawn_sample(self.perf_client.clone(), url, key, self.perf_tx.clone());
+        got
+    }
+
+    /// Fetch the model list of every managed-server connection, e.g. once
+    /// llama-server finished loading
+    pub fn refresh_local_models(&mut self) {
+        let names: Vec<String> = self
+            .settings
+            .connections
+            .iter()
+            .filter(|c| c.kind == ConnectionKind::LocalManaged)
+            .map(|c| c.name.clone())
+            .collect();
+        for name in names {
+            self.check_connection(&name);
+        }
+    }
+
+    /// `model` field for chat requests
+    pub fn chat_model_id(&self) -> String {
+        let conn = self.active_connection();
+        if !conn.model.is_empty() {
+            return conn.model;
+        }
+        self.served_model_id.clone().unwrap_or_else(|| "local".into())
+    }
+
+    /// Label recorded on replies: the local model file, or the remote model
+    pub fn reply_model_label(&self) -> Option<String> {
+        let conn = self.active_connection();
+        match conn.kind {
+            ConnectionKind::LocalManaged => self.current_model_name(),
+            _ if !conn.model.is_empty() => Some(conn.model),
+            _ => Some(conn.name),
+        }
     }
 
     /// Auto-stop server after inactivity
@@ -185,18 +752,493 @@ impl App {
         if self.settings.auto_stop_server && self.server_ready {
             let timeout = Duration::from_secs(self.settings.server_timeout_minutes as u64 * 60);
             if self.last_activity.elapsed() > timeout {
-                if let Some(mut child) = self.server_child.take() {
-                    let _ = child.kill();
-                    self.server_ready = false;
-                    self.server_status = ServerStatus::Stopped;
+                if self.server_child.is_some() {
+                    self.stop_server();
                     self.status = "Server auto-stopped (inactive)".into();
                 }
             }
         }
     }
 
+    /// Start a fresh, unsaved conversation
+    pub fn new_chat(&mut self) {
+        // Keep whatever has streamed so far before dropping the receiver
+        self.cancel_reply();
+        self.msgs.clear();
+        self.tree = Default::default();
+        self.path.clear();
+        self.msg_heights.clear();
+        self.input.clear();
+        self.editing = None;
+        self.rx = None;
+        self.current_conversation = None;
+        self.current_preset = self.settings.default_preset.clone();
+        self.select_persona(self.settings.default_persona.clone());
+        self.select_connection(&self.settings.default_connection.clone());
+        self.status = "New chat started".into();
+    }
+
+    /// Write the current messages into the conversation store
+    pub fn persist_chat(&mut self) {
+        if self.msgs.is_empty() {
+            return;
+        }
+        let now = crate::history::now_secs();
+        let model = self.reply_model_label();
+        let idx = self
+            .current_conversation
+            .as_ref()
+            .and_then(|id| self.conversations.iter().position(|c| c.id == *id));
+        let mut conv = match idx {
+            Some(i) => self.conversations.remove(i),
+            None => Conversation {
+                id: crate::history::new_conversation_id(),
+                title: String::new(),
+                created_at: now,
+                updated_at: now,
+                model: None,
+                preset: None,
+                persona: None,
+                connection: None,
+                msgs: vec![],
+                tree: Default::default(),
+            },
+        };
+        self.sync_tree();
+        conv.msgs = self.msgs.clone();
+        conv.tree = self.tree.clone();
+        conv.preset = Some(self.current_preset.clone());
+        conv.persona = self.current_persona.clone();
+        conv.connection = Some(self.current_connection.clone());
+        conv.updated_at = now;
+        if model.is_some() {
+            conv.model = model;
+        }
+        if conv.title.is_empty() {
+            conv.title = crate::history::default_title(&conv);
+        }
+        if let Err(e) = crate::history::save_conversation(&self.history_dir, &conv) {
+            self.status = format!("History save err: {e}");
+        }
+        self.current_conversation = Some(conv.id.clone());
+        self.conversations.insert(0, conv);
+        crate::history::enforce_history_limit(
+            &self.history_dir,
+            &mut self.conversations,
+            self.settings.max_chat_history,
+        );
+    }
+
+    /// Copy the shown messages into the tree along the current branch
+    fn sync_tree(&mut self) {
+        self.tree.sync(&mut self.path, &self.msgs);
+    }
+
+    /// Show the branches currently selected in the tree
+    fn load_active_branch(&mut self) {
+        self.path = self.tree.active_path();
+        self.msgs = self.tree.messages(&self.path);
+        self.editing = None;
+    }
+
+    /// Keep message `i` as it is and add `content` as an alternative to
+    /// it. An edited question is answered again right away.
+    pub fn branch_message(&mut self, i: usize, content: String) {
+        if self.rx.is_some() || i >= self.msgs.len() {
+            return;
+        }
+        self.sync_tree();
+        let mut msg = self.msgs[i].clone();
+        msg.content = content;
+        let is_user = msg.role == "user";
+        self.tree.push(self.tree.parent(self.path[i]), msg);
+        self.load_active_branch();
+        self.persist_chat();
+        if is_user {
+            self.mark_activity();
+            self.start_reply();
+        }
+    }
+
+    /// Step message `i` to one of its sibling branches
+    pub fn switch_branch(&mut self, i: usize, delta: isize) {
+        if self.rx.is_some() || i >= self.path.len() {
+            return;
+        }
+        self.sync_tree();
+        self.tree.select_sibling(self.path[i], delta);
+        self.load_active_branch();
+        self.persist_chat();
+    }
+
+    /// Stream a new assistant reply to the messages shown
+    pub fn start_reply(&mut self) {
+        self.msgs.push(Msg {
+            role: "assistant".into(),
+            content: String::new(),
+            model: self.reply_model_label(),
+            ..Default::default()
+        });
+        self.spawn_stream();
+        self.persist_chat();
+    }
+
+    /// Answer the messages before reply `i` again, keeping the old reply
+    /// as a sibling branch
+    pub fn regenerate(&mut self, i: usize) {
+        if self.rx.is_some() || self.msgs.get(i).is_none_or(|m| m.role != "assistant") {
+            return;
+        }
+        self.sync_tree();
+        self.msgs.truncate(i);
+        self.path.truncate(i);
+        self.editing = None;
+        self.mark_activity();
+        self.start_reply();
+    }
+
+    /// Let the model extend the last reply, e.g. after it hit max tokens
+    pub fn continue_reply(&mut self) {
+        if self.rx.is_some() || !self.active_connection().kind.supports_prefill() {
+            return;
+        }
+        match self.msgs.last_mut() {
+            Some(last) if last.role == "assistant" => last.finish = None,
+            _ => return,
+        }
+        self.mark_activity();
+        self.spawn_stream();
+    }
+
+    /// Stop waiting for the reply being streamed, keeping what arrived
+    pub fn cancel_reply(&mut self) {
+        if self.rx.is_none() {
+            return;
+        }
+        self.stop_stream();
+        if let Some(last) = self.msgs.last_mut()
+            && last.role == "assistant"
+        {
+            last.finish = Some(FinishReason::Cancel);
+        }
+        self.status = "Canceled".into();
+        self.persist_chat();
+    }
+
+    /// Drop the reply stream and close its connection
+    fn stop_stream(&mut self) {
+        self.rx = None;
+        if let Some(handle) = self.stream_cancel.take() {
+            handle.cancel();
+        }
+    }
+
+    /// Stream into the last message if it is a reply, sending everything
+    /// before it as history
+    fn spawn_stream(&mut self) {
+        let (tx, rx) = mpsc::channel::<StreamEvent>();
+        self.rx = Some(rx);
+        let (url, api_key) = self.chat_endpoint();
+        let kind = self.active_connection().kind;
+        let req = crate::models::ChatReq::new(kind, self.chat_model_id(), self.msgs.clone(), self.active_sampling());
+        let system_prompt = self.active_system_prompt();
+        self.stream_cancel = Some(crate::stream::stream_chat(&url, api_key, req, system_prompt, tx));
+        self.scroll_to_bottom = true;
+    }
+
+    /// Replace the current chat with a saved conversation
+    pub fn open_conversation(&mut self, id: &str) {
+        if self.current_conversation.as_deref() == Some(id) {
+            return;
+        }
+        self.cancel_reply();
+        if let Some(conv) = self.conversations.iter().find(|c| c.id == id) {
+            self.tree = if conv.tree.is_empty() {
+                crate::tree::ChatTree::from_linear(&conv.msgs)
+            } else {
+                conv.tree.clone()
+            };
+            self.path = self.tree.active_path();
+            self.msgs = self.tree.messages(&self.path);
+            self.msg_heights.clear();
+            self.scroll_to_bottom = true;
+            self.current_conversation = Some(conv.id.clone());
+            self.current_preset = conv
+                .preset
+                .clone()
+                .unwrap_or_else(|| self.settings.default_preset.clone());
+            self.current_persona = conv.persona.clone();
+            let connection = conv
+                .connection
+                .clone()
+                .unwrap_or_else(|| self.settings.default_connection.clone());
+            self.editing = None;
+            self.rx = None;
+            self.status = format!("Opened \"{}\"", conv.title);
+            self.select_connection(&connection);
+        }
+    }
+
+    pub fn rename_conversation(&mut self, id: &str, title: String) {
+        if let Some(conv) = self.conversations.iter_mut().find(|c| c.id == id) {
+            conv.title = title;
+            if let Err(e) = crate::history::save_conversation(&self.history_dir, conv) {
+                self.status = format!("History save err: {e}");
+            }
+        }
+    }
+
+    /// Point saved conversations whose `field` names `old` at `new`, after
+    /// a preset, persona or connection is renamed (`Some`) or deleted (`None`)
+    pub fn remap_conversations(&mut self, field: fn(&mut Conversation) -> &mut Option<String>, old: &str, new: Option<&str>) {
+        for conv in &mut self.conversations {
+            let value = field(conv);
+            if value.as_deref() != Some(old) {
+                continue;
+            }
+            *value = new.map(str::to_string);
+            if let Err(e) = crate::history::save_conversation(&self.history_dir, conv) {
+                self.status = format!("History save err: {e}");
+            }
+        }
+    }
+
+    pub fn delete_conversation(&mut self, id: &str) {
+        if let Err(e) = crate::history::delete_conversation(&self.history_dir, id) {
+            self.status = format!("History delete err: {e}");
+            return;
+        }
+        self.conversations.retain(|c| c.id != id);
+        if self.current_conversation.as_deref() == Some(id) {
+            self.current_conversation = None;
+            self.stop_stream();
+            self.msgs.clear();
+            self.tree = Default::default();
+            self.path.clear();
+            self.editing = None;
+        }
+    }
+
+    /// Sampling parameters of the preset selected for this conversation,
+    /// falling back to the default preset and then to built-in defaults
+    pub fn active_sampling(&self) -> SamplingParams {
+        let presets = &self.settings.sampling_presets;
+        presets
+            .iter()
+            .find(|p| p.name == self.current_preset)
+            .or_else(|| presets.iter().find(|p| p.name == self.settings.default_preset))
+            .map(|p| p.params.clone())
+            .unwrap_or_default()
+    }
+
+    /// Switch persona, adopting its sampling preset if it names one
+    pub fn select_persona(&mut self, name: Option<String>) {
+        let preset = name
+            .as_ref()
+            .and_then(|n| self.settings.personas.iter().find(|p| p.name == *n))
+            .and_then(|p| p.preset.clone());
+        if let Some(preset) = preset {
+            self.current_preset = preset;
+        }
+        self.current_persona = name;
+    }
+
+    /// System prompt of the current persona, if any
+    pub fn active_system_prompt(&self) -> Option<String> {
+        let name = self.current_persona.as_ref()?;
+        self.settings
+            .personas
+            .iter()
+            .find(|p| p.name == *name)
+            .map(|p| p.system_prompt.clone())
+    }
+
+    /// The chosen backend, or the one guessed for this machine under `Auto`
+    pub fn effective_backend(&self) -> Backend {
+        if self.backend == Backend::Auto {
+            guess_backend()
+        } else {
+            self.backend
+        }
+    }
+
+    /// Memory estimate for a model under the given launch profile. `Auto`
+    /// is passed on so a guessed GPU that could not be queried counts as CPU.
+    pub fn estimate_fit(&self, info: &GgufInfo, file_size: u64, profile: &LaunchProfile) -> FitEstimate {
+        crate::fit::estimate_fit(info, file_size, profile, &self.system_memory, self.backend)
+    }
+
+    /// Launch profile assigned to a model file, or the default profile
+    pub fn launch_profile_for(&self, model: &std::path::Path) -> LaunchProfile {
+        let file_name = model
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let name = self
+            .settings
+            .model_profiles
+            .get(&file_name)
+            .unwrap_or(&self.settings.default_launch_profile);
+        let profiles = &self.settings.launch_profiles;
+        profiles
+            .iter()
+            .find(|p| p.name == *name)
+            .or_else(|| profiles.iter().find(|p| p.name == self.settings.default_launch_profile))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Kill the running llama-server, if any
+    pub fn stop_server(&mut self) {
+        self.restart_at = None;
+        self.server_exited = None;
+        self.pending_switch = false;
+        if let Some((mut old, _)) = self.stopping_child.take() {
+            let _ = old.kill();
+            let _ = old.wait();
+        }
+        self.server_alive.store(false, Ordering::Relaxed);
+        if let Some(mut child) = self.server_child.take() {
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+        self.server_ready = false;
+        self.server_status = ServerStatus::Stopped;
+        self.status = "Server stopped".into();
+    }
+
+    /// Watch the llama-server child: report why it died and restart it
+    /// with backoff when the crash looks transient
+    pub fn supervise_server(&mut self) {
+        if let Some((old, deadline)) = self.stopping_child.as_mut() {
+            let exited = matches!(old.try_wait(), Ok(Some(_)));
+            if !exited && Instant::now() < *deadline {
+                return;
+            }
+            if !exited {
+                let _ = old.kill();
+                let _ = old.wait();
+            }
+            self.stopping_child = None;
+            if self.pending_switch {
+                self.pending_switch = false;
+                self.launch_server();
+                if matches!(self.server_status, ServerStatus::Starting) {
+                    let name = self.current_model_name().unwrap_or_default();
+                    self.server_status = ServerStatus::Switching(name);
+                }
+            }
+            return;
+        }
+
+        if let Some(at) = self.restart_at
+            && Instant::now() >= at
+        {
+            self.restart_at = None;
+            self.status = format!(
+                "Restarting server (attempt {}/{})",
+                self.restart_attempts, self.settings.max_restart_attempts
+            );
+            self.launch_server();
+            return;
+        }
+
+        if self.server_exited.is_none() {
+            let Some(child) = self.server_child.as_mut() else {
+                return;
+            };
+            let Ok(Some(exit)) = child.try_wait() else {
+                return;
+            };
+            self.server_child = None;
+            self.server_alive.store(false, Ordering::Relaxed);
+            self.server_ready = false;
+            self.server_exited = Some((exit, Instant::now() + LOG_DRAIN_GRACE));
+        }
+        // The line explaining the crash may still be in the pipe; wait for
+        // the log to close before deciding what went wrong
+        let Some((exit, deadline)) = self.server_exited else {
+            return;
+        };
+        if self.log_rx.is_some() && Instant::now() < deadline {
+            return;
+        }
+        self.server_exited = None;
+
+        let reason = match self.server_fault {
+            Some(fault) => fault.describe(self.server_port.unwrap_or(0)),
+            None => format!("llama-server exited ({exit})"),
+        };
+        // Another process took the automatically picked port between
+        // choosing it and binding; a new launch picks a different one
+        let port_lost = self.server_fault == Some(ServerFault::PortInUse) && self.settings.server_port == 0;
+        let can_restart = (port_lost || (self.server_fault.is_none() && self.settings.auto_restart_server))
+            && self.restart_attempts < self.settings.max_restart_attempts;
+        if port_lost && can_restart {
+            self.restart_attempts += 1;
+            self.restart_at = Some(Instant::now());
+            self.server_status = ServerStatus::Starting;
+            self.status = format!("Server err: {reason}; retrying on another port");
+        } else if can_restart {
+            let delay = crate::server::restart_backoff(self.restart_attempts);
+            self.restart_attempts += 1;
+            self.restart_at = Some(Instant::now() + delay);
+            self.server_status = ServerStatus::Starting;
+            self.status = format!("Server crashed ({reason}); restarting in {}s", delay.as_secs());
+        } else {
+            self.status = format!("Server err: {reason}");
+            self.server_status = ServerStatus::Error(reason);
+        }
+    }
+
+    /// File name of the model the server was started with, used to label
+    /// replies. The selection may already point at the next model while a
+    /// switch is pending, so it is only the last resort.
+    pub fn current_model_name(&self) -> Option<String> {
+        let file_name = |p: &std::path::Path| p.file_name().map(|s| s.to_string_lossy().to_string());
+        self.loaded_model
+            .as_deref()
+            .and_then(|m| file_name(std::path::Path::new(m)))
+            .or_else(|| self.served_model_id.clone())
+            .or_else(|| self.model_path.as_deref().and_then(file_name))
+    }
+
+    /// Select a model and, if a server is running, restart it with the new
+    /// model once the old process has shut down. The chat is left untouched.
+    pub fn switch_model(&mut self, path: PathBuf) {
+        let name = path
+            .file_name()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.model_path = Some(path);
+        self.model_file = name.clone();
+        self.model_repo = "(local)".into();
+        self.restart_at = None;
+
+        let Some(mut child) = self.server_child.take() else {
+            self.status = format!("Selected model: {name}");
+            return;
+        };
+        self.cancel_reply();
+        self.server_alive.store(false, Ordering::Relaxed);
+        self.server_ready = false;
+        crate::server::request_terminate(&mut child);
+        self.stopping_child = Some((child, Instant::now() + Duration::from_secs(10)));
+        self.pending_switch = true;
+        self.server_status = ServerStatus::Switching(name.clone());
+        self.status = format!("Switching to {name}…");
+    }
+
     /// Update last activity timestamp
     pub fn mark_activity(&mut self) {
         self.last_activity = Instant::now();
     }
 }
+
+impl Drop for App {
+    fn drop(&mut self) {
+        // Never leave an orphaned llama-server behind
+        self.downloads.stop_all();
+        self.stop_server();
+    }
+}
@@ -2,6 +2,11 @@ use crate::models::*;
 use eframe::egui::{self, Color32};
 
 impl eframe::App for crate::app::App {
+    fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        self.downloads.stop_all();
+        self.stop_server();
+    }
+
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // streaming updates
         if let Some(rx0) = self.rx.take() {
@@ -16,8 +21,34 @@ impl eframe::App for crate::app::App {
                             }
                         }
                     }
+                    StreamEvent::Finish(reason) => {
+                        if let Some(last) = self.msgs.last_mut()
+                            && last.role == "assistant"
+                        {
+                            last.finish = Some(reason);
+                        }
+                    }
+                    StreamEvent::Usage(usage) => {
+                        if let Some(last) = self.msgs.last_mut()
+                            && last.role == "assistant"
+                        {
+                            last.usage = Some(usage);
+                        }
+                    }
+                    StreamEvent::Timings(timings) => {
+                        if let Some(last) = self.msgs.last_mut()
+                            && last.role == "assistant"
+                        {
+                            last.timings = Some(timings);
+                        }
+                    }
                     StreamEvent::Error(e) => {
                         self.status = format!("Chat err: {e}");
+                        if let Some(last) = self.msgs.last_mut()
+                            && last.role == "assistant"
+                        {
+                            last.finish = Some(FinishReason::Error);
+                        }
                     }
                     StreamEvent::Done => {
                         done = true;
@@ -28,97 +59,130 @@ impl eframe::App for crate::app::App {
             if !done {
                 self.rx = Some(rx);
             } else {
+                self.stream_cancel = None;
                 self.status = "Idle".into();
+                self.persist_chat();
             }
         }
 
         // drain download events
-        if let Some(rx) = self.dl_rx.take() {
-            let mut rx = rx;
-            let mut events = Vec::new();
-            while let Ok(ev) = rx.try_recv() {
-                events.push(ev);
-            }
-            self.dl_rx = Some(rx);
-            for ev in events {
-                match ev {
-                    DownloadEvent::Done {
-                        kind: DownloadKind::Model,
-                        dest,
-                    } => {
-                        if let Some(p) = dest {
-                            self.model_path = Some(p);
-                        }
-                        self.model_progress = None;
-                        self.status = "Model ready".into();
-                        crate::scan::scan_downloaded_models(self);
-                    }
-                    DownloadEvent::Done {
-                        kind: DownloadKind::Runtime,
-                        ..
-                    } => {
-                        let bin_dir = self.runtime_dir.join("llama-bin");
-                        self.server_bin = crate::server::find_server_bin(&bin_dir);
-                        self.runtime_progress = None;
-                        self.status = "Runtime ready".into();
+        let events = self.downloads.pump(self.settings.max_concurrent_downloads);
+        for ev in events {
+            match ev {
+                DownloadEvent::Done {
+                    kind: DownloadKind::Model,
+                    dest,
+                    ..
+                } => {
+                    if let Some(p) = dest {
+                        self.model_path = Some(p);
                     }
-                    DownloadEvent::Progress {
-                        kind: DownloadKind::Runtime,
-                        current,
-                        total,
-                        stage,
-                    } => {
-                        self.runtime_progress = Some((current, total, stage.to_string()));
-                        self.status = format!(
-                            "Runtime {stage}: {} / {}",
-                            crate::download::human_size(current),
-                            total.map(crate::download::human_size).unwrap_or_else(|| "?".into())
-                        );
-                    }
-                    DownloadEvent::Progress {
-                        kind: DownloadKind::Model,
-                        current,
-                        total,
-                        stage,
-                    } => {
-                        self.model_progress = Some((current, total, stage.to_string()));
-                        self.status = format!(
-                            "Model {stage}: {} / {}",
-                            crate::download::human_size(current),
-                            total.map(crate::download::human_size).unwrap_or_else(|| "?".into())
-                        );
-                    }
-                    DownloadEvent::Error {
-                        kind: DownloadKind::Runtime,
-                        err,
-                    } => {
-                        self.runtime_progress = None;
-                        self.status = format!("Runtime err: {err}");
-                    }
-                    DownloadEvent::Error {
-                        kind: DownloadKind::Model,
-                        err,
-                    } => {
-                        self.model_progress = None;
-                        self.status = format!("Model err: {err}");
+                    self.status = "Model ready".into();
+                    crate::scan::scan_downloaded_models(self);
+                }
+                DownloadEvent::Done {
+                    kind: DownloadKind::Runtime,
+                    dest,
+                    ..
+                } => {
+                    self.detect_runtimes();
+                    self.status = "Runtime ready".into();
+                    // New builds take over unless the user pinned a version
+                    let installed = self
+                        .available_runtimes
+                        .iter()
+                        .find(|r| Some(&r.dir) == dest.as_ref())
+                        .map(|r| r.name.clone());
+                    if let Some(name) = installed
+                        && (!self.settings.runtime_pinned || self.current_runtime.is_none())
+                    {
+                        self.select_runtime(&name);
                     }
                 }
-                ctx.request_repaint();
+                DownloadEvent::Progress {
+                    kind: DownloadKind::Runtime,
+                    current,
+                    total,
+                    stage,
+                    ..
+                } => {
+                    self.status = format!(
+                        "Runtime {stage}: {} / {}",
+                        crate::download::human_size(current),
+                        total.map(crate::download::human_size).unwrap_or_else(|| "?".into())
+                    );
+                }
+                DownloadEvent::Progress {
+                    kind: DownloadKind::Model,
+                    current,
+                    total,
+                    stage,
+                    ..
+                } => {
+                    self.status = format!(
+                        "Model {stage}: {} / {}",
+                        crate::download::human_size(current),
+                        total.map(crate::download::human_size).unwrap_or_else(|| "?".into())
+                    );
+                }
+                DownloadEvent::Error {
+                    kind: DownloadKind::Runtime,
+                    err,
+                    ..
+                } => {
+                    self.status = format!("Runtime err: {err}");
+                }
+                DownloadEvent::Error {
+                    kind: DownloadKind::Model,
+                    err,
+                    ..
+                } => {
+                    self.status = format!("Model err: {err}");
+                }
+                DownloadEvent::Stopped { .. } => {}
             }
+            ctx.request_repaint();
+        }
+        if self.downloads.active_count() > 0 {
+            ctx.request_repaint_after(std::time::Duration::from_millis(250));
         }
 
         // Drain server logs
+        let mut became_ready = false;
+        let mut log_closed = false;
         if let Some(lrx) = &self.log_rx {
-            while let Ok(line) = lrx.try_recv() {
+            loop {
+                let line = match lrx.try_recv() {
+                    Ok(line) => line,
+                    Err(e) => {
+                        log_closed = e == std::sync::mpsc::TryRecvError::Disconnected;
+                        break;
+                    }
+                };
+                // Only startup errors explain an exit; once ready, the same
+                // wording can be a warning the server recovers from
+                if line.starts_with("[ERR]") && !self.server_ready && self.server_fault.is_none() {
+                    self.server_fault = crate::server::classify_fatal_line(&line);
+                }
                 if line.starts_with("[READY]") {
+                    self.restart_attempts = 0;
                     self.server_ready = true;
                     self.server_status = ServerStatus::Running;
                     self.status = "Server ready".into();
+                    became_ready = true;
+                }
+                if line.starts_with("[TIMEOUT]") && !self.server_ready {
+                    self.server_status = ServerStatus::Error("timed out waiting for llama-server".into());
+                    self.status = "Server err: startup timed out".into();
                 }
                 if let Some(rest) = line.strip_prefix("[MODEL] ") {
                     self.served_model_id = Some(rest.to_string());
                 }
+                if let Err(e) = self.log_file.write_line(&line) {
+                    self.status = format!("Log file err: {e}");
+                }
                 self.server_log.push(line);
+                self.server_log_seq += 1;
                 if self.server_log.len() > 2000 {
                     let drop = self.server_log.len() - 2000;
                     self.server_log.drain(0..drop);
@@ -126,10 +190,56 @@ impl eframe::App for crate::app::App {
                 ctx.request_repaint();
             }
         }
+        if log_closed {
+            self.log_rx = None;
+        }
+        let _ = self.log_file.flush();
+        if became_ready {
+            self.refresh_local_models();
+        }
 
         // Automatic server management
+        self.supervise_server();
+        if self.server_child.is_some()
+            || self.restart_at.is_some()
+            || self.stopping_child.is_some()
+            || self.server_exited.is_some()
+        {
+            ctx.request_repaint_after(std::time::Duration::from_millis(500));
+        }
         self.check_server_timeout();
 
+        self.check_runtime_updates(false);
+        self.refresh_models_if_due();
+        self.refresh_memory_if_due();
+        if self.poll_connection_health() {
+            ctx.request_repaint();
+        }
+        if self.poll_runtime_probes() {
+            ctx.request_repaint();
+        }
+        if self.probes_pending > 0 {
+            ctx.request_repaint_after(std::time::Duration::from_millis(250));
+        }
+        if self.poll_hf_headers() {
+            ctx.request_repaint();
+        }
+        if self.hf_headers.values().any(Option::is_none) {
+            ctx.request_repaint_after(std::time::Duration::from_millis(250));
+        }
+        if self.connection_health.values().any(|h| *h == ConnectionHealth::Checking) {
+            ctx.request_repaint_after(std::time::Duration::from_millis(250));
+        }
+        if self.poll_perf() {
+            ctx.request_repaint();
+        }
+        if self.show_perf {
+            ctx.request_repaint_after(std::time::Duration::from_secs(1));
+        }
+        if self.update_rx.is_some() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(500));
+        }
+
         // Auto-start server when user is active and has messages
         if !self.msgs.is_empty() && !self.input.is_empty() {
             self.mark_activity();
@@ -159,7 +269,14 @@ impl eframe::App for crate::app::App {
                             ServerStatus::Starting => {
                                 ui.colored_label(Color32::from_rgb(249, 226, 175), "⏳ Server Starting...");
                             }
-                            _ => {
+                            ServerStatus::Switching(name) => {
+                                ui.colored_label(Color32::from_rgb(249, 226, 175), format!("🔄 Loading {name}..."));
+                            }
+                            ServerStatus::Error(err) => {
+                                ui.colored_label(Color32::from_rgb(243, 139, 168), "❌ Server Error")
+                                    .on_hover_text(err);
+                            }
+                            ServerStatus::Stopped => {
                                 ui.colored_label(Color32::from_rgb(243, 139, 168), "🛑 Server Stopped");
                             }
                         }
@@ -176,12 +293,36 @@ impl eframe::App for crate::app::App {
                             self.show_settings = !self.show_settings;
                         }
 
-                        if self.server_ready {
+                        let active = self.downloads.active_count();
+                        let label = if active > 0 {
+                            format!("⬇️ Downloads ({active})")
+                        } else {
+                            "⬇️ Downloads".to_string()
+                        };
+                        if ui.add(crate::ui::light_button(&label, Color32::from_rgb(249, 226, 175))).clicked() {
+                            self.show_downloads = !self.show_downloads;
+                        }
+
+                        if ui.add(crate::ui::light_button("📈 Server", Color32::from_rgb(148, 226, 213))).clicked() {
+                            self.show_perf = !self.show_perf;
+                        }
+
+                        if ui.add(crate::ui::light_button("📜 Logs", Color32::from_rgb(186, 194, 222))).clicked() {
+                            self.show_logs = !self.show_logs;
+                        }
+
+                        if !self.update_dismissed
+                            && let Some(tag) = self.runtime_update().map(|r| r.tag_name.clone())
+                            && ui
+                                .add(crate::ui::light_button(&format!("⬆️ llama.cpp {tag} available"), Color32::from_rgb(203, 166, 247)))
+                                .clicked()
+                        {
+                            self.show_release_notes = !self.show_release_notes;
+                        }
+
+                        if self.server_ready || !self.using_local_server() {
                             if ui.add(crate::ui::light_button("💬 New Chat", Color32::from_rgb(166, 227, 161))).clicked() {
-                                self.msgs.clear();
-                                self.input.clear();
-                                self.editing = None;
-                                self.status = "New chat started".into();
+                                self.new_chat();
                             }
                         }
                     });
@@ -207,6 +348,58 @@ impl eframe::App for crate::app::App {
                 });
         }
 
+        if self.show_downloads {
+            let mut open = true;
+            egui::Window::new("⬇️ Downloads")
+                .open(&mut open)
+                .default_width(640.0)
+                .show(ctx, |ui| {
+                    crate::ui_downloads::render_downloads_panel(self, ui);
+                });
+            self.show_downloads = open;
+        }
+
+        if self.show_perf {
+            let mut open = true;
+            egui::Window::new("📈 Server")
+                .open(&mut open)
+                .default_width(960.0)
+                .default_height(520.0)
+                .show(ctx, |ui| {
+                    crate::ui_perf::render_server_panel(self, ui);
+                });
+            self.show_perf = open;
+        }
+
+        if self.show_logs {
+            let mut open = true;
+            egui::Window::new("📜 Server log")
+                .open(&mut open)
+                .default_width(900.0)
+                .default_height(480.0)
+                .show(ctx, |ui| {
+                    crate::ui_logs::render_log_viewer(self, ui);
+                });
+            self.show_logs &= open;
+        }
+
+        if self.show_release_notes {
+            let mut open = true;
+            egui::Window::new("⬆️ llama.cpp update")
+                .open(&mut open)
+                .default_width(520.0)
+                .show(ctx, |ui| {
+                    crate::ui_settings::render_release_notes(self, ui);
+                });
+            self.show_release_notes &= open;
+        }
+
+        egui::SidePanel::left("history")
+            .default_width(220.0)
+            .show(ctx, |ui| {
+                crate::ui_history::render_history_panel(self, ui);
+            });
+
         egui::CentralPanel::default().show(ctx, |ui| {
             crate::ui_chat::render_chat_panel(self, ui);
         });
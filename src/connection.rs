@@ -0,0 +1,58 @@
+use crate::models::{Connection, ConnectionKind, ModelEntry, ModelList};
+use std::{sync::mpsc, time::Duration};
+
+/// Trim trailing slashes and a trailing `/v1`, since request paths add it
+pub fn normalize_base_url(url: &str) -> String {
+    let url = url.trim().trim_end_matches('/');
+    url.strip_suffix("/v1").unwrap_or(url).to_string()
+}
+
+/// Base URL and API key requests to `conn` should use; the managed server
+/// lives at whatever address it was last started on
+pub fn endpoint(conn: &Connection, local_url: &str) -> (String, Option<String>) {
+    let url = match conn.kind {
+        ConnectionKind::LocalManaged => local_url.to_string(),
+        _ => normalize_base_url(&conn.url),
+    };
+    let key = Some(conn.api_key.trim().to_string()).filter(|k| !k.is_empty());
+    (url, key)
+}
+
+pub fn get(url: &str, api_key: Option<&str>) -> reqwest::blocking::RequestBuilder {
+    get_with(&reqwest::blocking::Client::new(), url, api_key)
+}
+
+/// `get` on a client kept by the caller, so repeated polls reuse its
+/// connection pool
+pub fn get_with(client: &reqwest::blocking::Client, url: &str, api_key: Option<&str>) -> reqwest::blocking::RequestBuilder {
+    let req = client.get(url).timeout(Duration::from_secs(10));
+    match api_key {
+        Some(k) => req.bearer_auth(k),
+        None => req,
+    }
+}
+
+/// Models the server lists, or why it is unreachable
+pub fn fetch_models(url: &str, api_key: Option<&str>) -> Result<Vec<ModelEntry>, String> {
+    let resp = get(&format!("{url}/v1/models"), api_key)
+        .send()
+        .map_err(|e| e.to_string())?;
+    let status = resp.status();
+    if !status.is_success() {
+        return Err(format!("HTTP {status}"));
+    }
+    resp.json::<ModelList>()
+        .map(|l| l.data)
+        .map_err(|e| format!("unexpected /v1/models response: {e}"))
+}
+
+pub fn spawn_health_check(
+    name: String,
+    url: String,
+    api_key: Option<String>,
+    tx: mpsc::Sender<(String, Result<Vec<ModelEntry>, String>)>,
+) {
+    std::thread::spawn(move || {
+        let _ = tx.send((name, fetch_models(&url, api_key.as_deref())));
+    });
+}
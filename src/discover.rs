@@ -0,0 +1,292 @@
+use crate::models::{Backend, RuntimeInfo, RuntimeSource};
+use crate::server::find_server_bin;
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::mpsc,
+    time::Duration,
+};
+
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Places llama.cpp usually ends up in when installed or built by hand
+fn common_locations() -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = vec![
+        "/usr/local/bin".into(),
+        "/usr/bin".into(),
+        "/opt/homebrew/bin".into(),
+        "/opt/llama.cpp/build/bin".into(),
+    ];
+    if let Some(home) = directories::BaseDirs::new().map(|d| d.home_dir().to_path_buf()) {
+        dirs.push(home.join(".local").join("bin"));
+        dirs.push(home.join("llama.cpp").join("build").join("bin"));
+        dirs.push(home.join("src").join("llama.cpp").join("build").join("bin"));
+    }
+    #[cfg(target_os = "windows")]
+    {
+        dirs.push(r"C:\Program Files\llama.cpp".into());
+        dirs.push(r"C:\llama.cpp\build\bin\Release".into());
+    }
+    dirs
+}
+
+/// What `--version` reported for a binary, if it answered
+pub type VersionProbe = (PathBuf, Option<(String, String, Backend)>);
+
+/// llama-server binaries outside the app's own runtime folder. Versions are
+/// left unknown; `spawn_version_probes` fills them in.
+pub fn discover_system_runtimes(custom: &[PathBuf]) -> Vec<RuntimeInfo> {
+    let mut found: Vec<(PathBuf, RuntimeSource)> = Vec::new();
+    for p in custom {
+        let bin = if p.is_dir() { find_server_bin(p) } else { Some(p.clone()).filter(|p| p.is_file()) };
+        if let Some(bin) = bin {
+            found.push((bin, RuntimeSource::Custom));
+        }
+    }
+    if let Ok(all) = which::which_all("llama-server") {
+        found.extend(all.map(|p| (p, RuntimeSource::Path)));
+    }
+    for dir in common_locations() {
+        if let Some(bin) = find_server_bin(&dir) {
+            found.push((bin, RuntimeSource::System));
+        }
+    }
+
+    let mut seen: Vec<PathBuf> = Vec::new();
+    let mut out = Vec::new();
+    for (bin, source) in found {
+        let key = bin.canonicalize().unwrap_or_else(|_| bin.clone());
+        if seen.contains(&key) {
+            continue;
+        }
+        seen.push(key);
+        out.push(RuntimeInfo {
+            name: bin.display().to_string(),
+            dir: bin.parent().map(Path::to_path_buf).unwrap_or_default(),
+            path: bin,
+            version: "Unknown".to_string(),
+            backend: Backend::Auto,
+            source,
+            build_info: String::new(),
+        });
+    }
+    out
+}
+
+/// Probe each binary off the UI thread, sending one result per binary
+pub fn spawn_version_probes(bins: Vec<PathBuf>, tx: mpsc::Sender<VersionProbe>) {
+    std::thread::spawn(move || {
+        for bin in bins {
+            let result = probe_version(&bin);
+            if tx.send((bin, result)).is_err() {
+                return;
+            }
+        }
+    });
+}
+
+/// Run `llama-server --version` and read the build number, the compiler
+/// line and, from the backend init messages, the GPU backend
+pub fn probe_version(bin: &Path) -> Option<(String, String, Backend)> {
+    let child = Command::new(bin)
+        .arg("--version")
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .ok()?;
+    let pid = child.id();
+    // Waiting on a thread keeps both pipes drained, so a chatty build
+    // cannot fill one and stall before exiting
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let _ = tx.send(child.wait_with_output());
+    });
+    let out = match rx.recv_timeout(PROBE_TIMEOUT) {
+        Ok(out) => out.ok()?,
+        Err(_) => {
+            kill_pid(pid);
+            return None;
+        }
+    };
+    let text = format!(
+        "{}{}",
+        String::from_utf8_lossy(&out.stdout),
+        String::from_utf8_lossy(&out.stderr)
+    );
+    parse_version_output(&text)
+}
+
+/// Kill a probe that hangs; its `Child` belongs to the waiting thread
+fn kill_pid(pid: u32) {
+    #[cfg(unix)]
+    let mut cmd = {
+        let mut c = Command::new("kill");
+        c.args(["-KILL", &pid.to_string()]);
+        c
+    };
+    #[cfg(windows)]
+    let mut cmd = {
+        let mut c = Command::new("taskkill");
+        c.args(["/F", "/PID", &pid.to_string()]);
+        c
+    };
+    let _ = cmd.stdout(Stdio::null()).stderr(Stdio::null()).status();
+}
+
+/// `version: 4567 (abcdef12)` / `built with cc 13.2 for x86_64-linux-gnu`.
+/// Builds made outside git report `version: 0 (unknown)`, which is kept as
+/// text so it never compares as a release.
+fn parse_version_output(text: &str) -> Option<(String, String, Backend)> {
+    let version = text.lines().find_map(|l| {
+        let rest = l.trim().strip_prefix("version:")?.trim();
+        let number = rest.split_whitespace().next()?;
+        Some(if number != "0" && number.chars().all(|c| c.is_ascii_digit()) {
+            format!("b{number}")
+        } else {
+            rest.to_string()
+        })
+    })?;
+    let build_info = text
+        .lines()
+        .find(|l| l.trim().starts_with("built with"))
+        .map(|l| l.trim().to_string())
+        .unwrap_or_default();
+    let backend = version_backend(text, &build_info);
+    Some((version, build_info, backend))
+}
+
+/// GPU backend named by the device init and `load_backend` lines. Only
+/// those lines are read, so install paths and compiler names can't match.
+fn version_backend(text: &str, build_info: &str) -> Backend {
+    let mut loaded_cpu = false;
+    for line in text.lines().map(str::trim) {
+        let name = if let Some(rest) = line.strip_prefix("load_backend: loaded ") {
+            rest.split_whitespace().next().unwrap_or("")
+        } else if line.starts_with("ggml_cuda_init: found") {
+            // HIP builds share the CUDA code and count "ROCm devices"
+            if line.contains("ROCm") { "ROCm" } else { "CUDA" }
+        } else if line.starts_with("ggml_vulkan:") {
+            "Vulkan"
+        } else if line.starts_with("ggml_metal") {
+            "Metal"
+        } else {
+            continue;
+        };
+        match name {
+            "CUDA" => return Backend::Cuda,
+            "ROCm" | "HIP" => return Backend::Hip,
+            "Vulkan" => return Backend::Vulkan,
+            "Metal" | "MTL" => return Backend::Metal,
+            "CPU" => loaded_cpu = true,
+            _ => {}
+        }
+    }
+    if loaded_cpu {
+        Backend::Cpu
+    } else if build_info.contains("arm64-apple-darwin") {
+        // Apple Silicon builds link Metal in without printing anything
+        Backend::Metal
+    } else {
+        Backend::Auto
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> (String, String, Backend) {
+        parse_version_output(text).unwrap()
+    }
+
+    #[test]
+    fn cuda_build() {
+        let (version, build, backend) = parse(
+            "ggml_cuda_init: GGML_CUDA_FORCE_MMQ:    no
+ggml_cuda_init: GGML_CUDA_FORCE_CUBLAS: no
+ggml_cuda_init: found 1 CUDA devices:
+  Device 0: NVIDIA GeForce RTX 4090, compute capability 8.9, VMM: yes
+load_backend: loaded CUDA backend from /opt/llama/libggml-cuda.so
+load_backend: loaded CPU backend from /opt/llama/libggml-cpu-haswell.so
+version: 5560 (7e00e60e)
+built with cc (Ubuntu 11.4.0-1ubuntu1~22.04) 11.4.0 for x86_64-linux-gnu
+",
+        );
+        assert_eq!(version, "b5560");
+        assert_eq!(build, "built with cc (Ubuntu 11.4.0-1ubuntu1~22.04) 11.4.0 for x86_64-linux-gnu");
+        assert!(backend == Backend::Cuda);
+    }
+
+    #[test]
+    fn rocm_build() {
+        let (_, _, backend) = parse(
+            "ggml_cuda_init: GGML_CUDA_FORCE_MMQ:    no
+ggml_cuda_init: found 1 ROCm devices:
+  Device 0: AMD Radeon RX 7900 XTX, gfx1100 (0x1100), VMM: no, Wave Size: 32
+load_backend: loaded ROCm backend from /opt/llama/libggml-hip.so
+version: 6123 (79c1160b)
+built with cc (GCC) 14.2.1 for x86_64-pc-linux-gnu
+",
+        );
+        assert!(backend == Backend::Hip);
+    }
+
+    #[test]
+    fn vulkan_build() {
+        let (version, _, backend) = parse(
+            "ggml_vulkan: Found 1 Vulkan devices:
+ggml_vulkan: 0 = AMD Radeon RX 6800 (RADV NAVI21) (radv) | uma: 0 | fp16: 1 | warp size: 64 | shared memory: 65536 | int dot: 1 | matrix cores: none
+load_backend: loaded Vulkan backend from /home/me/llama/libggml-vulkan.so
+load_backend: loaded CPU backend from /home/me/llama/libggml-cpu-alderlake.so
+version: 6123 (79c1160b)
+built with cc (Ubuntu 13.3.0-6ubuntu2~24.04) 13.3.0 for x86_64-linux-gnu
+",
+        );
+        assert_eq!(version, "b6123");
+        assert!(backend == Backend::Vulkan);
+    }
+
+    #[test]
+    fn metal_build() {
+        let (version, _, backend) = parse(
+            "version: 5450 (a8ea03d8)
+built with Apple clang version 16.0.0 (clang-1600.0.26.6) for arm64-apple-darwin24.4.0
+",
+        );
+        assert_eq!(version, "b5450");
+        assert!(backend == Backend::Metal);
+    }
+
+    #[test]
+    fn cpu_build_ignores_paths_that_name_a_backend() {
+        let (_, _, backend) = parse(
+            "load_backend: loaded RPC backend from /opt/cuda-tools/llama/libggml-rpc.so
+load_backend: loaded CPU backend from /opt/cuda-tools/llama/libggml-cpu-haswell.so
+version: 6000 (1d72c841)
+built with cc (Debian 12.2.0-14) 12.2.0 for x86_64-linux-gnu
+",
+        );
+        assert!(backend == Backend::Cpu);
+    }
+
+    #[test]
+    fn older_builds() {
+        // Before backends were loaded at runtime nothing names the backend
+        let (version, build, backend) = parse(
+            "version: 3235 (88540445)
+built with MSVC 19.39.33523.0 for x64
+",
+        );
+        assert_eq!(version, "b3235");
+        assert_eq!(build, "built with MSVC 19.39.33523.0 for x64");
+        assert!(backend == Backend::Auto);
+
+        // Built outside a git checkout, as distro packages are
+        let (version, _, _) = parse("version: 0 (unknown)\nbuilt with cc 14.2.0 for x86_64-linux-gnu\n");
+        assert_eq!(version, "0 (unknown)");
+        assert!(crate::runtime::tag_number(&version).is_none());
+
+        assert!(parse_version_output("llama-server: unknown option --version").is_none());
+    }
+}
@@ -0,0 +1,299 @@
+use crate::models::{DownloadEvent, DownloadJob, DownloadKind, JobControl, JobId, JobSpec, JobState};
+use std::{
+    fs,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+/// Tracks every model and runtime download by ID, runs at most
+/// `max_concurrent` of them at once and feeds their events back to the app
+pub struct DownloadManager {
+    pub jobs: Vec<DownloadJob>,
+    next_id: JobId,
+    tx: mpsc::Sender<DownloadEvent>,
+    rx: mpsc::Receiver<DownloadEvent>,
+}
+
+impl Default for DownloadManager {
+    fn default() -> Self {
+        let (tx, rx) = mpsc::channel();
+        Self {
+            jobs: vec![],
+            next_id: 1,
+            tx,
+            rx,
+        }
+    }
+}
+
+impl DownloadManager {
+    pub fn enqueue(&mut self, kind: DownloadKind, label: String, spec: JobSpec) -> JobId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.jobs.push(DownloadJob {
+            id,
+            kind,
+            label,
+            spec,
+            state: JobState::Queued,
+            current: 0,
+            total: None,
+            stage: "queued",
+            error: None,
+            speed: 0.0,
+            sample: (Instant::now(), 0),
+            control: JobControl::default(),
+        });
+        id
+    }
+
+    fn get_mut(&mut self, id: JobId) -> Option<&mut DownloadJob> {
+        self.jobs.iter_mut().find(|j| j.id == id)
+    }
+
+    /// Job already downloading (or waiting to download) the same target
+    pub fn find_active(&self, spec_dest: &std::path::Path) -> Option<&DownloadJob> {
+        self.jobs.iter().find(|j| {
+            matches!(j.state, JobState::Queued | JobState::Running | JobState::Paused)
+                && match &j.spec {
+                    JobSpec::Model { files, .. } => files.first().is_some_and(|(_, d)| d == spec_dest),
+                    JobSpec::Runtime { bin_dir, .. } => bin_dir == spec_dest,
+                }
+        })
+    }
+
+    pub fn active_count(&self) -> usize {
+        self.jobs
+            .iter()
+            .filter(|j| matches!(j.state, JobState::Queued | JobState::Running))
+            .count()
+    }
+
+    pub fn pause(&mut self, id: JobId) {
+        if let Some(job) = self.get_mut(id) {
+            match job.state {
+                JobState::Running => {
+                    job.control.stop();
+                    job.state = JobState::Paused;
+                    job.stage = "pausing";
+                    job.speed = 0.0;
+                }
+                JobState::Queued => {
+                    job.state = JobState::Paused;
+                    job.stage = "paused";
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Resuming waits until the previous worker has let go of the file
+    pub fn resume(&mut self, id: JobId) {
+        if let Some(job) = self.get_mut(id)
+            && job.state == JobState::Paused
+            && job.stage != "pausing"
+        {
+            job.state = JobState::Queued;
+            job.stage = "queued";
+        }
+    }
+
+    pub fn cancel(&mut self, id: JobId) {
+        if let Some(job) = self.get_mut(id) {
+            // A job that is still pausing has a worker holding the file too
+            let was_running = job.state == JobState::Running || job.stage == "pausing";
+            match job.state {
+                JobState::Queued | JobState::Running | JobState::Paused => {
+                    job.control.stop();
+                    job.state = JobState::Cancelled;
+                    job.stage = if was_running { "cancelling" } else { "cancelled" };
+                    job.speed = 0.0;
+                }
+                _ => return,
+            }
+            // A running worker still owns the file; it is removed once the
+            // worker reports that it stopped
+            if !was_running {
+                remove_partial(&job.spec);
+            }
+        }
+    }
+
+    pub fn retry(&mut self, id: JobId) {
+        if let Some(job) = self.get_mut(id)
+            && matches!(job.state, JobState::Failed | JobState::Cancelled)
+            && job.stage != "cancelling"
+        {
+            job.state = JobState::Queued;
+            job.stage = "queued";
+            job.error = None;
+        }
+    }
+
+    /// Forget finished, failed and cancelled jobs
+    pub fn clear_finished(&mut self) {
+        self.jobs.retain(|j| {
+            matches!(j.state, JobState::Queued | JobState::Running | JobState::Paused)
+        });
+    }
+
+    fn start(&mut self, idx: usize) {
+        let tx = self.tx.clone();
+        let job = &mut self.jobs[idx];
+        job.control = JobControl::default();
+        job.state = JobState::Running;
+        job.stage = "starting";
+        job.speed = 0.0;
+        job.sample = (Instant::now(), job.current);
+        let control = job.control.clone();
+        match job.spec.clone() {
+            JobSpec::Model { repo, files } => {
+                crate::model_download::spawn_model_download(job.id, repo, files, control, tx);
+            }
+            JobSpec::Runtime {
+                url,
+                zip_path,
+                bin_dir,
+            } => {
+                crate::spawn::spawn_runtime_download(job.id, url, zip_path, bin_dir, control, tx);
+            }
+        }
+    }
+
+    /// Apply worker events to the job list, start queued jobs while there is
+    /// room, and hand the events back so the app can react to them
+    pub fn pump(&mut self, max_concurrent: usize) -> Vec<DownloadEvent> {
+        let mut events = Vec::new();
+        while let Ok(ev) = self.rx.try_recv() {
+            self.apply(&ev);
+            events.push(ev);
+        }
+
+        let running = self.jobs.iter().filter(|j| j.state == JobState::Running).count();
+        let free = max_concurrent.max(1).saturating_sub(running);
+        let queued: Vec<usize> = self
+            .jobs
+            .iter()
+            .enumerate()
+            .filter(|(_, j)| j.state == JobState::Queued)
+            .map(|(i, _)| i)
+            .take(free)
+            .collect();
+        for idx in queued {
+            self.start(idx);
+        }
+        events
+    }
+
+    fn apply(&mut self, ev: &DownloadEvent) {
+        let Some(job) = self.get_mut(ev.job()) else {
+            return;
+        };
+        match ev {
+            DownloadEvent::Progress {
+                current,
+                total,
+                stage,
+                ..
+            } => {
+                // Late progress from a worker that is about to stop
+                if job.state != JobState::Running {
+                    return;
+                }
+                if *stage != job.stage {
+                    job.sample = (Instant::now(), *current);
+                    job.speed = 0.0;
+                }
+                job.current = *current;
+                job.total = *total;
+                job.stage = stage;
+                let elapsed = job.sample.0.elapsed();
+                if elapsed >= Duration::from_millis(500) {
+                    let delta = current.saturating_sub(job.sample.1) as f64;
+                    let rate = delta / elapsed.as_secs_f64();
+                    job.speed = if job.speed > 0.0 {
+                        job.speed * 0.7 + rate * 0.3
+                    } else {
+                        rate
+                    };
+                    job.sample = (Instant::now(), *current);
+                }
+            }
+            DownloadEvent::Done { .. } => {
+                job.state = JobState::Completed;
+                job.stage = "done";
+                job.speed = 0.0;
+                if let Some(t) = job.total {
+                    job.current = t;
+                }
+            }
+            DownloadEvent::Error { err, .. } => {
+                match job.state {
+                    JobState::Running => job.state = JobState::Failed,
+                    JobState::Paused => job.stage = "paused",
+                    JobState::Cancelled => {
+                        remove_partial(&job.spec);
+                        job.stage = "cancelled";
+                    }
+                    _ => {}
+                }
+                job.error = Some(err.clone());
+                job.speed = 0.0;
+            }
+            DownloadEvent::Stopped { .. } => {
+                job.speed = 0.0;
+                match job.state {
+                    JobState::Cancelled => {
+                        remove_partial(&job.spec);
+                        job.current = 0;
+                        job.stage = "cancelled";
+                    }
+                    JobState::Paused => job.stage = "paused",
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Ask every running worker to stop, e.g. when the app closes
+    pub fn stop_all(&self) {
+        for job in &self.jobs {
+            if job.state == JobState::Running {
+                job.control.stop();
+            }
+        }
+    }
+}
+
+fn remove_partial(spec: &JobSpec) {
+    match spec {
+        JobSpec::Model { files, .. } => {
+            for (_, dest) in files {
+                let _ = fs::remove_file(crate::model_download::part_path(dest));
+            }
+        }
+        JobSpec::Runtime { zip_path, .. } => {
+            let _ = fs::remove_file(zip_path);
+        }
+    }
+}
+
+/// Remaining time at the current speed, if it can be estimated
+pub fn eta(job: &DownloadJob) -> Option<Duration> {
+    let total = job.total?;
+    if job.speed < 1.0 || job.current >= total {
+        return None;
+    }
+    Some(Duration::from_secs_f64((total - job.current) as f64 / job.speed))
+}
+
+pub fn human_duration(d: Duration) -> String {
+    let s = d.as_secs();
+    if s >= 3600 {
+        format!("{}h {:02}m", s / 3600, (s % 3600) / 60)
+    } else if s >= 60 {
+        format!("{}m {:02}s", s / 60, s % 60)
+    } else {
+        format!("{s}s")
+    }
+}
@@ -0,0 +1,267 @@
+use crate::models::{Backend, FitEstimate, FitVerdict, GgufInfo, LaunchProfile, SystemMemory};
+use std::process::Command;
+
+const MB: u64 = 1024 * 1024;
+/// GPU memory kept free for the driver, display and other programs
+const VRAM_RESERVE: u64 = 512 * MB;
+/// Share of RAM Metal lets a single process wire on unified memory
+const UNIFIED_GPU_SHARE: f64 = 0.75;
+
+pub fn detect_system_memory(backend: Backend) -> SystemMemory {
+    let mut sys = sysinfo::System::new();
+    sys.refresh_memory();
+    let mut mem = SystemMemory {
+        ram_total: sys.total_memory(),
+        ram_available: sys.available_memory(),
+        ..Default::default()
+    };
+    match backend {
+        Backend::Metal => {
+            mem.unified = true;
+            mem.vram_total = Some((mem.ram_total as f64 * UNIFIED_GPU_SHARE) as u64);
+            mem.gpu_name = Some("Apple GPU (unified memory)".into());
+        }
+        Backend::Cpu => {}
+        _ => {
+            if let Some((name, total)) = query_nvidia_smi().or_else(query_rocm_smi) {
+                mem.gpu_name = Some(name);
+                mem.vram_total = Some(total);
+            }
+        }
+    }
+    mem
+}
+
+/// Name and total memory of the largest NVIDIA GPU
+fn query_nvidia_smi() -> Option<(String, u64)> {
+    let out = Command::new("nvidia-smi")
+        .args(["--query-gpu=name,memory.total", "--format=csv,noheader,nounits"])
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&out.stdout)
+        .lines()
+        .filter_map(|line| {
+            let (name, mib) = line.rsplit_once(',')?;
+            Some((name.trim().to_string(), mib.trim().parse::<u64>().ok()? * MB))
+        })
+        .max_by_key(|(_, total)| *total)
+}
+
+/// Name and total memory of the largest AMD GPU
+fn query_rocm_smi() -> Option<(String, u64)> {
+    let out = Command::new("rocm-smi")
+        .args(["--showproductname", "--showmeminfo", "vram", "--json"])
+        .output()
+        .ok()?;
+    if !out.status.success() {
+        return None;
+    }
+    // {"card0": {"Card series": "...", "VRAM Total Memory (B)": "17163091968", ...}}
+    let cards: serde_json::Map<String, serde_json::Value> = serde_json::from_slice(&out.stdout).ok()?;
+    cards
+        .values()
+        .filter_map(|card| {
+            let total = card["VRAM Total Memory (B)"].as_str()?.trim().parse::<u64>().ok()?;
+            let name = ["Card series", "Card model", "Device Name"]
+                .iter()
+                .find_map(|k| card[*k].as_str())
+                .unwrap_or("AMD GPU");
+            Some((name.trim().to_string(), total))
+        })
+        .max_by_key(|(_, total)| *total)
+}
+
+/// Memory free for new programs right now
+pub fn available_ram() -> u64 {
+    let mut sys = sysinfo::System::new();
+    sys.refresh_memory();
+    sys.available_memory()
+}
+
+/// Bytes per element of a llama.cpp KV cache type
+fn kv_type_bytes(ty: &str) -> f64 {
+    match ty {
+        "f32" => 4.0,
+        "q8_0" => 34.0 / 32.0,
+        "q4_0" | "iq4_nl" => 18.0 / 32.0,
+        "q4_1" => 20.0 / 32.0,
+        "q5_0" => 22.0 / 32.0,
+        "q5_1" => 24.0 / 32.0,
+        _ => 2.0,
+    }
+}
+
+/// Predict memory needs of a model. `file_size` is the size of all shards;
+/// it stands in for the weights when the tensor table is incomplete, as
+/// with a remote header that only covers the first shard.
+pub fn estimate_fit(
+    info: &GgufInfo,
+    file_size: u64,
+    profile: &LaunchProfile,
+    mem: &SystemMemory,
+    backend: Backend,
+) -> FitEstimate {
+    let layers = info.block_count.unwrap_or(0) as u32;
+    let ctx = if profile.ctx_size > 0 {
+        profile.ctx_size as u64
+    } else {
+        info.context_length.unwrap_or(4096)
+    };
+
+    // KV cache: one K and one V row of n_embd_kv values per token per layer
+    let heads = info.head_count.unwrap_or(1).max(1);
+    let heads_kv = info.head_count_kv.unwrap_or(heads);
+    let head_dim = info.embedding_length.unwrap_or(0) / heads;
+    let k_width = info.key_length.unwrap_or(head_dim) * heads_kv;
+    let v_width = info.value_length.unwrap_or(head_dim) * heads_kv;
+    let kv_per_layer = (ctx as f64
+        * (k_width as f64 * kv_type_bytes(&profile.cache_type_k)
+            + v_width as f64 * kv_type_bytes(&profile.cache_type_v))) as u64;
+    let kv_cache = kv_per_layer * layers as u64;
+
+    let tensor_bytes: u64 = info.tensors.iter().map(|t| t.bytes).sum();
+    let complete = tensor_bytes as f64 >= file_size as f64 * 0.9;
+    let weights = if complete { tensor_bytes } else { file_size };
+    let layer_weights: u64 = if complete {
+        info.tensors.iter().filter(|t| t.layer.is_some()).map(|t| t.bytes).sum()
+    } else {
+        weights / (layers as u64 + 1) * layers as u64
+    };
+    let per_layer = layer_weights / layers.max(1) as u64;
+    // Embeddings and output head; llama.cpp offloads the output as one
+    // extra layer
+    let rest = weights.saturating_sub(layer_weights);
+
+    // Logits and activations for one batch, plus a fixed scratch area
+    let batch = if profile.batch_size > 0 { profile.batch_size as u64 } else { 2048 };
+    let overhead = 256 * MB + info.vocab_size.unwrap_or(32000) * batch.min(512) * 4;
+
+    let budget = match (backend, mem.vram_total) {
+        (Backend::Cpu, _) | (_, None) => 0,
+        (_, Some(total)) => total.saturating_sub(VRAM_RESERVE + overhead),
+    };
+    let mut suggested_ngl = 0;
+    let mut used = 0;
+    while suggested_ngl < layers && used + per_layer + kv_per_layer <= budget {
+        used += per_layer + kv_per_layer;
+        suggested_ngl += 1;
+    }
+    if suggested_ngl == layers && layers > 0 && used + rest <= budget {
+        used += rest;
+        suggested_ngl += 1;
+    }
+
+    let offloaded = suggested_ngl > 0;
+    let vram_needed = if offloaded { used + overhead } else { 0 };
+    let total = weights + kv_cache + overhead;
+    let ram_needed = if mem.unified {
+        total
+    } else {
+        total.saturating_sub(vram_needed)
+    };
+
+    // Without a GPU memory reading, -ngl 0 would only be a guess. With
+    // `Auto` the GPU itself was only guessed, so no reading means CPU.
+    let vram_unknown = mem.vram_total.is_none() && !matches!(backend, Backend::Cpu | Backend::Auto);
+    let verdict = if vram_unknown {
+        FitVerdict::VramUnknown
+    } else if ram_needed > mem.ram_total {
+        FitVerdict::TooBig
+    } else if layers > 0 && suggested_ngl > layers {
+        FitVerdict::Gpu
+    } else if offloaded {
+        FitVerdict::Partial
+    } else if ram_needed > mem.ram_available {
+        FitVerdict::Tight
+    } else {
+        FitVerdict::Cpu
+    };
+
+    FitEstimate {
+        weights,
+        kv_cache,
+        overhead,
+        ctx,
+        vram_needed,
+        ram_needed,
+        suggested_ngl,
+        layers,
+        verdict,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::GgufTensor;
+
+    const GB: u64 = 1024 * MB;
+
+    /// Ten 100 MB layers plus 100 MB of embeddings and output
+    fn model() -> GgufInfo {
+        let mut tensors: Vec<GgufTensor> = (0..10)
+            .map(|i| GgufTensor { layer: Some(i), elements: 0, bytes: 100 * MB })
+            .collect();
+        tensors.push(GgufTensor { layer: None, elements: 0, bytes: 100 * MB });
+        GgufInfo {
+            block_count: Some(10),
+            embedding_length: Some(512),
+            head_count: Some(8),
+            vocab_size: Some(32000),
+            tensors,
+            ..Default::default()
+        }
+    }
+
+    fn mem(ram_total: u64, ram_available: u64, vram_total: Option<u64>) -> SystemMemory {
+        SystemMemory { ram_total, ram_available, vram_total, ..Default::default() }
+    }
+
+    #[test]
+    fn verdicts() {
+        let cases = [
+            ("everything on the GPU", mem(16 * GB, 16 * GB, Some(8 * GB)), Backend::Cuda, FitVerdict::Gpu),
+            ("some layers on the GPU", mem(16 * GB, 16 * GB, Some(GB)), Backend::Vulkan, FitVerdict::Partial),
+            ("CPU backend ignores the GPU", mem(16 * GB, 16 * GB, Some(8 * GB)), Backend::Cpu, FitVerdict::Cpu),
+            ("guessed GPU without a reading", mem(16 * GB, 16 * GB, None), Backend::Auto, FitVerdict::Cpu),
+            ("guessed GPU with a reading", mem(16 * GB, 16 * GB, Some(8 * GB)), Backend::Auto, FitVerdict::Gpu),
+            ("chosen GPU without a reading", mem(16 * GB, 16 * GB, None), Backend::Cuda, FitVerdict::VramUnknown),
+            ("not enough free RAM", mem(16 * GB, GB, None), Backend::Cpu, FitVerdict::Tight),
+            ("not enough RAM at all", mem(GB, GB, None), Backend::Cpu, FitVerdict::TooBig),
+        ];
+        let info = model();
+        let profile = LaunchProfile::default();
+        for (name, mem, backend, want) in cases {
+            let est = estimate_fit(&info, 1100 * MB, &profile, &mem, backend);
+            assert!(est.verdict == want, "{name}");
+        }
+    }
+
+    #[test]
+    fn layer_counts() {
+        let info = model();
+        let profile = LaunchProfile::default();
+        let full = estimate_fit(&info, 1100 * MB, &profile, &mem(16 * GB, 16 * GB, Some(8 * GB)), Backend::Cuda);
+        assert_eq!((full.layers, full.suggested_ngl), (10, 11));
+        assert_eq!(full.weights, 1100 * MB);
+
+        let partial = estimate_fit(&info, 1100 * MB, &profile, &mem(16 * GB, 16 * GB, Some(GB)), Backend::Cuda);
+        assert!(partial.suggested_ngl > 0 && partial.suggested_ngl < 10);
+        assert!(partial.vram_needed <= GB - VRAM_RESERVE);
+        assert_eq!(partial.ram_needed + partial.vram_needed, partial.weights + partial.kv_cache + partial.overhead);
+
+        let cpu = estimate_fit(&info, 1100 * MB, &profile, &mem(16 * GB, 16 * GB, None), Backend::Cpu);
+        assert_eq!((cpu.suggested_ngl, cpu.vram_needed), (0, 0));
+    }
+
+    #[test]
+    fn file_size_stands_in_for_a_partial_tensor_table() {
+        let mut info = model();
+        info.tensors.truncate(2);
+        let est = estimate_fit(&info, 1100 * MB, &LaunchProfile::default(), &mem(16 * GB, 16 * GB, None), Backend::Cpu);
+        assert_eq!(est.weights, 1100 * MB);
+    }
+}
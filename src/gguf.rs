@@ -0,0 +1,501 @@
+use crate::models::{GgufInfo, GgufTensor};
+use std::{
+    fs,
+    io::{self, BufReader, Read},
+    path::Path,
+};
+
+const GGUF_MAGIC: &[u8; 4] = b"GGUF";
+/// Guard against corrupt files claiming absurd lengths
+const MAX_STRING_LEN: u64 = 64 * 1024 * 1024;
+
+#[derive(Clone, Debug)]
+enum Value {
+    Uint(u64),
+    Int(i64),
+    Float(f64),
+    Bool(bool),
+    Str(String),
+    /// Arrays are summarized; only their element count is kept
+    Array(u64),
+}
+
+impl Value {
+    fn as_u64(&self) -> Option<u64> {
+        match self {
+            Value::Uint(v) => Some(*v),
+            Value::Int(v) if *v >= 0 => Some(*v as u64),
+            _ => None,
+        }
+    }
+
+    fn display(&self) -> String {
+        match self {
+            Value::Uint(v) => v.to_string(),
+            Value::Int(v) => v.to_string(),
+            Value::Float(v) => format!("{v}"),
+            Value::Bool(v) => v.to_string(),
+            Value::Str(s) if s.chars().count() > 120 => {
+                let short: String = s.chars().take(120).collect();
+                format!("{short}…")
+            }
+            Value::Str(s) => s.clone(),
+            Value::Array(n) => format!("[{n} items]"),
+        }
+    }
+}
+
+struct Reader<R> {
+    inner: R,
+    version: u32,
+}
+
+impl<R: Read> Reader<R> {
+    fn bytes<const N: usize>(&mut self) -> anyhow::Result<[u8; N]> {
+        let mut buf = [0u8; N];
+        self.inner.read_exact(&mut buf)?;
+        Ok(buf)
+    }
+
+    fn u32(&mut self) -> anyhow::Result<u32> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn u64(&mut self) -> anyhow::Result<u64> {
+        Ok(u64::from_le_bytes(self.bytes()?))
+    }
+
+    /// Lengths and counts were 32-bit in GGUF v1
+    fn len(&mut self) -> anyhow::Result<u64> {
+        if self.version == 1 {
+            Ok(self.u32()? as u64)
+        } else {
+            self.u64()
+        }
+    }
+
+    fn string(&mut self) -> anyhow::Result<String> {
+        let len = self.len()?;
+        if len > MAX_STRING_LEN {
+            anyhow::bail!("string of {len} bytes in header");
+        }
+        let mut buf = vec![0u8; len as usize];
+        self.inner.read_exact(&mut buf)?;
+        Ok(String::from_utf8_lossy(&buf).into_owned())
+    }
+
+    fn skip(&mut self, n: u64) -> anyhow::Result<()> {
+        let skipped = io::copy(&mut (&mut self.inner).take(n), &mut io::sink())?;
+        if skipped < n {
+            anyhow::bail!("unexpected end of header");
+        }
+        Ok(())
+    }
+
+    fn value(&mut self, ty: u32) -> anyhow::Result<Value> {
+        Ok(match ty {
+            0 => Value::Uint(self.bytes::<1>()?[0] as u64),
+            1 => Value::Int(self.bytes::<1>()?[0] as i8 as i64),
+            2 => Value::Uint(u16::from_le_bytes(self.bytes()?) as u64),
+            3 => Value::Int(i16::from_le_bytes(self.bytes()?) as i64),
+            4 => Value::Uint(self.u32()? as u64),
+            5 => Value::Int(i32::from_le_bytes(self.bytes()?) as i64),
+            6 => Value::Float(f32::from_le_bytes(self.bytes()?) as f64),
+            7 => Value::Bool(self.bytes::<1>()?[0] != 0),
+            8 => Value::Str(self.string()?),
+            9 => {
+                let item_ty = self.u32()?;
+                let count = self.len()?;
+                self.skip_array(item_ty, count)?;
+                Value::Array(count)
+            }
+            10 => Value::Uint(self.u64()?),
+            11 => Value::Int(i64::from_le_bytes(self.bytes()?)),
+            12 => Value::Float(f64::from_le_bytes(self.bytes()?)),
+            _ => anyhow::bail!("unknown GGUF value type {ty}"),
+        })
+    }
+
+    /// Tokenizer vocabularies are large arrays; step over them without
+    /// keeping anything
+    fn skip_array(&mut self, item_ty: u32, count: u64) -> anyhow::Result<()> {
+        let fixed = match item_ty {
+            0 | 1 | 7 => Some(1),
+            2 | 3 => Some(2),
+            4..=6 => Some(4),
+            10..=12 => Some(8),
+            _ => None,
+        };
+        if let Some(size) = fixed {
+            let total = count
+                .checked_mul(size)
+                .ok_or_else(|| anyhow::anyhow!("array of {count} items in header"))?;
+            return self.skip(total);
+        }
+        for _ in 0..count {
+            match item_ty {
+                8 => {
+                    let len = self.len()?;
+                    self.skip(len)?;
+                }
+                _ => {
+                    self.value(item_ty)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// (elements per block, bytes per block) for a ggml tensor type
+fn ggml_type_size(ty: u32) -> Option<(u64, u64)> {
+    Some(match ty {
+        0 => (1, 4),      // F32
+        1 => (1, 2),      // F16
+        2 => (32, 18),    // Q4_0
+        3 => (32, 20),    // Q4_1
+        6 => (32, 22),    // Q5_0
+        7 => (32, 24),    // Q5_1
+        8 => (32, 34),    // Q8_0
+        9 => (32, 36),    // Q8_1
+        10 => (256, 84),  // Q2_K
+        11 => (256, 110), // Q3_K
+        12 => (256, 144), // Q4_K
+        13 => (256, 176), // Q5_K
+        14 => (256, 210), // Q6_K
+        15 => (256, 292), // Q8_K
+        16 => (256, 66),  // IQ2_XXS
+        17 => (256, 74),  // IQ2_XS
+        18 => (256, 98),  // IQ3_XXS
+        19 => (256, 50),  // IQ1_S
+        20 => (32, 18),   // IQ4_NL
+        21 => (256, 110), // IQ3_S
+        22 => (256, 82),  // IQ2_S
+        23 => (256, 136), // IQ4_XS
+        24 => (1, 1),     // I8
+        25 => (1, 2),     // I16
+        26 => (1, 4),     // I32
+        27 => (1, 8),     // I64
+        28 => (1, 8),     // F64
+        29 => (256, 56),  // IQ1_M
+        30 => (1, 2),     // BF16
+        34 => (256, 54),  // TQ1_0
+        35 => (256, 66),  // TQ2_0
+        39 => (32, 17),   // MXFP4
+        _ => return None,
+    })
+}
+
+/// Name of `general.file_type` (llama_ftype)
+pub fn file_type_name(ft: u64) -> String {
+    let name = match ft {
+        0 => "F32",
+        1 => "F16",
+        2 => "Q4_0",
+        3 => "Q4_1",
+        7 => "Q8_0",
+        8 => "Q5_0",
+        9 => "Q5_1",
+        10 => "Q2_K",
+        11 => "Q3_K_S",
+        12 => "Q3_K_M",
+        13 => "Q3_K_L",
+        14 => "Q4_K_S",
+        15 => "Q4_K_M",
+        16 => "Q5_K_S",
+        17 => "Q5_K_M",
+        18 => "Q6_K",
+        19 => "IQ2_XXS",
+        20 => "IQ2_XS",
+        21 => "Q2_K_S",
+        22 => "IQ3_XS",
+        23 => "IQ3_XXS",
+        24 => "IQ1_S",
+        25 => "IQ4_NL",
+        26 => "IQ3_S",
+        27 => "IQ3_M",
+        28 => "IQ2_S",
+        29 => "IQ2_M",
+        30 => "IQ4_XS",
+        31 => "IQ1_M",
+        32 => "BF16",
+        36 => "TQ1_0",
+        37 => "TQ2_0",
+        38 => "MXFP4_MOE",
+        _ => return format!("type {ft}"),
+    };
+    name.to_string()
+}
+
+/// Read the key/value metadata and tensor table of a GGUF file. Only the
+/// header is touched; tensor data is never loaded.
+pub fn read_gguf_info(path: &Path) -> anyhow::Result<GgufInfo> {
+    read_gguf_header(BufReader::new(fs::File::open(path)?))
+}
+
+/// Parse a GGUF header from the start of any stream, e.g. an HTTP body
+pub fn read_gguf_header(inner: impl Read) -> anyhow::Result<GgufInfo> {
+    let mut r = Reader { inner, version: 0 };
+    if &r.bytes::<4>()? != GGUF_MAGIC {
+        anyhow::bail!("not a GGUF file");
+    }
+    r.version = r.u32()?;
+    if !(1..=3).contains(&r.version) {
+        anyhow::bail!("unsupported GGUF version {}", r.version);
+    }
+    let tensor_count = r.len()?;
+    let kv_count = r.len()?;
+
+    let mut kv: Vec<(String, Value)> = Vec::new();
+    for _ in 0..kv_count {
+        let key = r.string()?;
+        let ty = r.u32()?;
+        let value = r.value(ty)?;
+        kv.push((key, value));
+    }
+
+    let mut tensors = Vec::new();
+    for _ in 0..tensor_count {
+        let name = r.string()?;
+        let n_dims = r.u32()?;
+        let mut elements: u64 = 1;
+        for _ in 0..n_dims {
+            elements = elements
+                .checked_mul(r.len()?)
+                .ok_or_else(|| anyhow::anyhow!("tensor {name} has too many elements"))?;
+        }
+        let ty = r.u32()?;
+        let _offset = r.u64()?;
+        let bytes = match ggml_type_size(ty) {
+            Some((block, size)) => elements
+                .div_ceil(block)
+                .checked_mul(size)
+                .ok_or_else(|| anyhow::anyhow!("tensor {name} is too large"))?,
+            None => 0,
+        };
+        tensors.push(GgufTensor {
+            layer: layer_of(&name),
+            elements,
+            bytes,
+        });
+    }
+
+    let get = |key: &str| kv.iter().find(|(k, _)| k == key).map(|(_, v)| v);
+    let get_str = |key: &str| match get(key) {
+        Some(Value::Str(s)) => Some(s.clone()),
+        _ => None,
+    };
+    let architecture = get_str("general.architecture");
+    let arch_u64 = |suffix: &str| {
+        architecture
+            .as_ref()
+            .and_then(|a| get(&format!("{a}.{suffix}")))
+            .and_then(|v| v.as_u64())
+    };
+
+    let parameter_count = get("general.parameter_count")
+        .and_then(|v| v.as_u64())
+        .or_else(|| count_params(&tensors));
+
+    Ok(GgufInfo {
+        version: r.version,
+        name: get_str("general.name"),
+        parameter_count,
+        file_type: get("general.file_type").and_then(|v| v.as_u64()).map(file_type_name),
+        context_length: arch_u64("context_length"),
+        block_count: arch_u64("block_count"),
+        embedding_length: arch_u64("embedding_length"),
+        head_count: arch_u64("attention.head_count"),
+        head_count_kv: arch_u64("attention.head_count_kv"),
+        key_length: arch_u64("attention.key_length"),
+        value_length: arch_u64("attention.value_length"),
+        chat_template: get_str("tokenizer.chat_template"),
+        tokenizer: get_str("tokenizer.ggml.model"),
+        vocab_size: match get("tokenizer.ggml.tokens") {
+            Some(Value::Array(n)) => Some(*n),
+            _ => None,
+        },
+        architecture,
+        metadata: kv.iter().map(|(k, v)| (k.clone(), v.display())).collect(),
+        tensors,
+    })
+}
+
+/// Parameters as the sum of tensor elements, for files that do not store
+/// `general.parameter_count`
+fn count_params(tensors: &[GgufTensor]) -> Option<u64> {
+    let total = tensors.iter().fold(0u64, |acc, t| acc.saturating_add(t.elements));
+    (total > 0).then_some(total)
+}
+
+/// Block index of tensors named like `blk.12.attn_q.weight`
+fn layer_of(name: &str) -> Option<u32> {
+    name.strip_prefix("blk.")?.split('.').next()?.parse().ok()
+}
+
+/// Info for a model, adding the tensors stored in the other shards of a
+/// split set to those of the first file
+pub fn read_model_info(parts: &[std::path::PathBuf]) -> anyhow::Result<GgufInfo> {
+    let first = parts.first().ok_or_else(|| anyhow::anyhow!("no files"))?;
+    let mut info = read_gguf_info(first)?;
+    for part in &parts[1..] {
+        if let Ok(extra) = read_gguf_info(part) {
+            info.tensors.extend(extra.tensors);
+        }
+    }
+    // The first shard only counted its own tensors
+    if parts.len() > 1 && !info.metadata.iter().any(|(k, _)| k == "general.parameter_count") {
+        info.parameter_count = count_params(&info.tensors);
+    }
+    Ok(info)
+}
+
+/// Compact parameter count such as "7.24B"
+pub fn human_params(n: u64) -> String {
+    let f = n as f64;
+    if f >= 1e9 {
+        format!("{:.2}B", f / 1e9)
+    } else if f >= 1e6 {
+        format!("{:.1}M", f / 1e6)
+    } else {
+        n.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a minimal GGUF v3 header
+    #[derive(Default)]
+    struct Builder {
+        kv: Vec<u8>,
+        n_kv: u64,
+        tensors: Vec<u8>,
+        n_tensors: u64,
+    }
+
+    fn put_str(buf: &mut Vec<u8>, s: &str) {
+        buf.extend((s.len() as u64).to_le_bytes());
+        buf.extend(s.as_bytes());
+    }
+
+    impl Builder {
+        fn kv_str(mut self, key: &str, value: &str) -> Self {
+            put_str(&mut self.kv, key);
+            self.kv.extend(8u32.to_le_bytes());
+            put_str(&mut self.kv, value);
+            self.n_kv += 1;
+            self
+        }
+
+        fn kv_u32(mut self, key: &str, value: u32) -> Self {
+            put_str(&mut self.kv, key);
+            self.kv.extend(4u32.to_le_bytes());
+            self.kv.extend(value.to_le_bytes());
+            self.n_kv += 1;
+            self
+        }
+
+        /// Array header only; `payload` is whatever follows it
+        fn kv_array(mut self, key: &str, item_ty: u32, count: u64, payload: &[u8]) -> Self {
+            put_str(&mut self.kv, key);
+            self.kv.extend(9u32.to_le_bytes());
+            self.kv.extend(item_ty.to_le_bytes());
+            self.kv.extend(count.to_le_bytes());
+            self.kv.extend(payload);
+            self.n_kv += 1;
+            self
+        }
+
+        fn tensor(mut self, name: &str, dims: &[u64], ty: u32) -> Self {
+            put_str(&mut self.tensors, name);
+            self.tensors.extend((dims.len() as u32).to_le_bytes());
+            for d in dims {
+                self.tensors.extend(d.to_le_bytes());
+            }
+            self.tensors.extend(ty.to_le_bytes());
+            self.tensors.extend(0u64.to_le_bytes());
+            self.n_tensors += 1;
+            self
+        }
+
+        fn build(&self) -> Vec<u8> {
+            let mut out = GGUF_MAGIC.to_vec();
+            out.extend(3u32.to_le_bytes());
+            out.extend(self.n_tensors.to_le_bytes());
+            out.extend(self.n_kv.to_le_bytes());
+            out.extend(&self.kv);
+            out.extend(&self.tensors);
+            out
+        }
+    }
+
+    fn sample() -> Builder {
+        Builder::default()
+            .kv_str("general.architecture", "llama")
+            .kv_u32("llama.block_count", 2)
+            .kv_array("tokenizer.ggml.tokens", 8, 2, &[1, 0, 0, 0, 0, 0, 0, 0, b'a', 1, 0, 0, 0, 0, 0, 0, 0, b'b'])
+            .tensor("blk.0.attn_q.weight", &[256, 4], 12)
+            .tensor("output.weight", &[32, 2], 8)
+    }
+
+    #[test]
+    fn reads_metadata_and_tensors() {
+        let info = read_gguf_header(&sample().build()[..]).unwrap();
+        assert_eq!(info.architecture.as_deref(), Some("llama"));
+        assert_eq!(info.block_count, Some(2));
+        assert_eq!(info.vocab_size, Some(2));
+        assert_eq!(info.tensors.len(), 2);
+        assert_eq!(info.tensors[0].layer, Some(0));
+        assert_eq!(info.tensors[0].bytes, 4 * 144);
+        assert_eq!(info.tensors[1].layer, None);
+        assert_eq!(info.tensors[1].bytes, 2 * 34);
+        assert_eq!(info.parameter_count, Some(1024 + 64));
+    }
+
+    #[test]
+    fn truncated_input_is_an_error() {
+        let data = sample().build();
+        for len in 0..data.len() {
+            assert!(read_gguf_header(&data[..len]).is_err(), "prefix of {len} bytes");
+        }
+    }
+
+    #[test]
+    fn rejects_bad_magic() {
+        let mut data = sample().build();
+        data[0] = b'X';
+        assert!(read_gguf_header(&data[..]).is_err());
+    }
+
+    #[test]
+    fn huge_array_count_is_an_error() {
+        let data = Builder::default().kv_array("bad", 10, u64::MAX, &[]).build();
+        assert!(read_gguf_header(&data[..]).is_err());
+    }
+
+    #[test]
+    fn huge_tensor_is_an_error() {
+        let data = Builder::default().tensor("blk.0.w", &[u64::MAX, 4], 0).build();
+        assert!(read_gguf_header(&data[..]).is_err());
+        let data = Builder::default().tensor("blk.0.w", &[u64::MAX / 2], 0).build();
+        assert!(read_gguf_header(&data[..]).is_err());
+    }
+
+    #[test]
+    fn split_set_counts_every_shard() {
+        let dir = std::env::temp_dir().join(format!("gguf-split-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let first = dir.join("m-00001-of-00002.gguf");
+        let second = dir.join("m-00002-of-00002.gguf");
+        fs::write(&first, sample().build()).unwrap();
+        fs::write(&second, Builder::default().tensor("blk.1.attn_q.weight", &[256, 4], 12).build()).unwrap();
+
+        let info = read_model_info(&[first, second]).unwrap();
+        let _ = fs::remove_dir_all(&dir);
+        assert_eq!(info.tensors.len(), 3);
+        assert_eq!(info.parameter_count, Some(1024 + 64 + 1024));
+        assert_eq!(info.tensors[2].layer, Some(1));
+    }
+}
@@ -1,4 +1,5 @@
-use crate::models::{HFFile, HFModel, HFModelInfo};
+use crate::models::{GgufInfo, HFFile, HFModel, HFModelInfo};
+use std::sync::mpsc;
 
 pub fn hf_search_models(q: &str) -> anyhow::Result<Vec<String>> {
     if q.trim().is_empty() {
@@ -14,7 +15,7 @@ pub fn hf_search_models(q: &str) -> anyhow::Result<Vec<String>> {
 
 pub fn hf_fetch_files(model: &str) -> anyhow::Result<Vec<HFFile>> {
     let url = format!(
-        "https://huggingface.co/api/models/{}?expand[]=siblings",
+        "https://huggingface.co/api/models/{}?blobs=true",
         model
     );
     let info: HFModelInfo = reqwest::blocking::get(url)?.json()?;
@@ -24,3 +25,28 @@ pub fn hf_fetch_files(model: &str) -> anyhow::Result<Vec<HFFile>> {
         .filter(|f| f.rfilename.to_lowercase().ends_with(".gguf"))
         .collect())
 }
+
+pub fn hf_resolve_url(repo: &str, file: &str) -> String {
+    format!("https://huggingface.co/{repo}/resolve/main/{file}?download=true")
+}
+
+/// A finished header read: repo, file and the parsed header or an error
+pub type HeaderFetch = (String, String, Result<GgufInfo, String>);
+
+/// Read the GGUF header of a repo file without downloading its weights
+pub fn hf_fetch_gguf_info(repo: &str, file: &str) -> anyhow::Result<GgufInfo> {
+    let resp = reqwest::blocking::Client::new()
+        .get(hf_resolve_url(repo, file))
+        .header(reqwest::header::RANGE, "bytes=0-67108863")
+        .send()?
+        .error_for_status()?;
+    crate::gguf::read_gguf_header(resp)
+}
+
+/// Read a header off the UI thread; it can take a 64 MB download
+pub fn spawn_gguf_info(repo: String, file: String, tx: mpsc::Sender<HeaderFetch>) {
+    std::thread::spawn(move || {
+        let result = hf_fetch_gguf_info(&repo, &file).map_err(|e| e.to_string());
+        let _ = tx.send((repo, file, result));
+    });
+}
@@ -0,0 +1,106 @@
+use crate::models::Conversation;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+pub fn new_conversation_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!("{nanos:x}")
+}
+
+fn conversation_path(dir: &Path, id: &str) -> PathBuf {
+    dir.join(format!("{id}.json"))
+}
+
+/// Load every saved conversation, most recently updated first
+pub fn load_conversations(dir: &Path) -> Vec<Conversation> {
+    let mut list = Vec::new();
+    if let Ok(read) = fs::read_dir(dir) {
+        for ent in read.flatten() {
+            let p = ent.path();
+            if p.extension().and_then(|s| s.to_str()) != Some("json") {
+                continue;
+            }
+            if let Ok(content) = fs::read_to_string(&p)
+                && let Ok(conv) = serde_json::from_str::<Conversation>(&content)
+            {
+                list.push(conv);
+            }
+        }
+    }
+    list.sort_by_key(|c| std::cmp::Reverse(c.updated_at));
+    list
+}
+
+pub fn save_conversation(dir: &Path, conv: &Conversation) -> anyhow::Result<()> {
+    fs::create_dir_all(dir)?;
+    let content = serde_json::to_string_pretty(conv)?;
+    let path = conversation_path(dir, &conv.id);
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, content)?;
+    fs::rename(tmp, path)?;
+    Ok(())
+}
+
+pub fn delete_conversation(dir: &Path, id: &str) -> anyhow::Result<()> {
+    let path = conversation_path(dir, id);
+    if path.exists() {
+        fs::remove_file(path)?;
+    }
+    Ok(())
+}
+
+/// Drop the oldest conversations so that at most `max` remain.
+/// `list` must be sorted most recent first.
+pub fn enforce_history_limit(dir: &Path, list: &mut Vec<Conversation>, max: usize) {
+    if list.len() <= max {
+        return;
+    }
+    for conv in list.drain(max..) {
+        let _ = delete_conversation(dir, &conv.id);
+    }
+}
+
+/// Title derived from the first user message
+pub fn default_title(conv: &Conversation) -> String {
+    let first = conv
+        .msgs
+        .iter()
+        .find(|m| m.role == "user")
+        .map(|m| m.content.trim())
+        .unwrap_or("");
+    let line = first.lines().next().unwrap_or("");
+    if line.is_empty() {
+        return "New chat".into();
+    }
+    let mut title: String = line.chars().take(40).collect();
+    if line.chars().count() > 40 {
+        title.push('…');
+    }
+    title
+}
+
+pub fn relative_time(ts: u64) -> String {
+    let age = now_secs().saturating_sub(ts);
+    if age < 60 {
+        "just now".into()
+    } else if age < 3600 {
+        format!("{}m ago", age / 60)
+    } else if age < 86400 {
+        format!("{}h ago", age / 3600)
+    } else {
+        format!("{}d ago", age / 86400)
+    }
+}
@@ -0,0 +1,166 @@
+use std::{
+    fs,
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+pub const LOGS_DIR: &str = "logs";
+const LOG_NAME: &str = "server";
+/// Rotate once the current file passes this size
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+/// Rotated files kept besides the current one
+const KEEP_ROTATED: usize = 4;
+
+/// Appends llama-server output to `logs/server.log`, moving full files to
+/// `server.1.log` … `server.4.log` so the last few sessions survive a crash
+pub struct LogWriter {
+    dir: PathBuf,
+    file: Option<BufWriter<fs::File>>,
+    size: u64,
+    max_bytes: u64,
+}
+
+impl LogWriter {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            file: None,
+            size: 0,
+            max_bytes: MAX_LOG_BYTES,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, n: usize) -> PathBuf {
+        match n {
+            0 => self.dir.join(format!("{LOG_NAME}.log")),
+            n => self.dir.join(format!("{LOG_NAME}.{n}.log")),
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file = None;
+        for n in (1..=KEEP_ROTATED).rev() {
+            let from = self.path(n - 1);
+            if from.exists() {
+                fs::rename(from, self.path(n))?;
+            }
+        }
+        self.size = 0;
+        Ok(())
+    }
+
+    pub fn write_line(&mut self, line: &str) -> io::Result<()> {
+        if self.size >= self.max_bytes {
+            self.rotate()?;
+        }
+        if self.file.is_none() {
+            fs::create_dir_all(&self.dir)?;
+            let file = fs::OpenOptions::new().create(true).append(true).open(self.path(0))?;
+            self.size = file.metadata()?.len();
+            self.file = Some(BufWriter::new(file));
+        }
+        let entry = format!("{} {line}\n", utc_timestamp(crate::history::now_secs()));
+        if let Some(f) = self.file.as_mut() {
+            f.write_all(entry.as_bytes())?;
+        }
+        self.size += entry.len() as u64;
+        Ok(())
+    }
+
+    /// Called after each batch of lines so a crash loses nothing
+    pub fn flush(&mut self) -> io::Result<()> {
+        match self.file.as_mut() {
+            Some(f) => f.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// `YYYY-MM-DD HH:MM:SS` in UTC for seconds since the Unix epoch
+pub fn utc_timestamp(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let rem = secs % 86400;
+    // Civil date from day count (H. Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02} {:02}:{:02}:{:02}",
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_utc_timestamps() {
+        assert_eq!(utc_timestamp(0), "1970-01-01 00:00:00");
+        assert_eq!(utc_timestamp(951_782_400), "2000-02-29 00:00:00");
+        assert_eq!(utc_timestamp(1_709_251_199), "2024-02-29 23:59:59");
+        assert_eq!(utc_timestamp(1_792_195_200), "2026-10-17 00:00:00");
+        assert_eq!(utc_timestamp(4_102_444_800), "2100-01-01 00:00:00");
+    }
+
+    fn read(w: &LogWriter, n: usize) -> String {
+        fs::read_to_string(w.path(n)).unwrap_or_default()
+    }
+
+    #[test]
+    fn rotates_newest_first_and_drops_the_oldest() {
+        let dir = std::env::temp_dir().join(format!("log-rotate-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut w = LogWriter {
+            max_bytes: 100,
+            ..LogWriter::new(dir.clone())
+        };
+        // Each entry is a 19-byte timestamp, a space, 60 bytes and a newline,
+        // so every file holds two entries before it passes 100 bytes
+        for i in 0..14 {
+            w.write_line(&format!("{i:02}{}", "x".repeat(58))).unwrap();
+        }
+        w.flush().unwrap();
+
+        let first_entry = |n| read(&w, n).lines().next().map(|l| l[20..22].to_string());
+        assert_eq!(first_entry(0).as_deref(), Some("12"));
+        assert_eq!(first_entry(1).as_deref(), Some("10"));
+        assert_eq!(first_entry(2).as_deref(), Some("08"));
+        assert_eq!(first_entry(3).as_deref(), Some("06"));
+        assert_eq!(first_entry(4).as_deref(), Some("04"));
+        assert!(!w.path(KEEP_ROTATED + 1).exists());
+        for n in 1..=KEEP_ROTATED {
+            let len = fs::metadata(w.path(n)).unwrap().len();
+            assert!((100..200).contains(&len), "server.{n}.log is {len} bytes");
+        }
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn continues_an_existing_file() {
+        let dir = std::env::temp_dir().join(format!("log-append-test-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut w = LogWriter::new(dir.clone());
+        w.write_line("first").unwrap();
+        w.flush().unwrap();
+        let mut w = LogWriter::new(dir.clone());
+        w.write_line("second").unwrap();
+        w.flush().unwrap();
+        let text = read(&w, 0);
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.lines().next().unwrap().ends_with(" first"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
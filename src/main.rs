@@ -1,18 +1,35 @@
 mod models;
 mod download;
+mod download_manager;
 mod unzip;
 mod spawn;
 mod model_download;
 mod runtime;
+mod discover;
 mod scan;
+mod shards;
+mod gguf;
+mod fit;
+mod sse;
 mod stream;
+mod markdown;
+mod connection;
 mod server;
+mod metrics;
 mod hf;
+mod history;
+mod logs;
+mod tree;
 mod ui;
 mod ui_top;
 mod ui_models;
 mod ui_chat;
 mod ui_settings;
+mod ui_history;
+mod ui_markdown;
+mod ui_downloads;
+mod ui_perf;
+mod ui_logs;
 mod app;
 mod app_impl;
 
@@ -0,0 +1,352 @@
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag, TagEnd};
+use std::collections::HashMap;
+
+/// A run of inline text sharing one style
+#[derive(Clone, Default)]
+pub struct Span {
+    pub text: String,
+    pub bold: bool,
+    pub italic: bool,
+    pub strike: bool,
+    pub code: bool,
+    pub link: Option<String>,
+}
+
+#[derive(Clone)]
+pub enum Block {
+    Heading(u8, Vec<Span>),
+    Paragraph(Vec<Span>),
+    Code { lang: String, code: String },
+    /// `start` is the first number of an ordered list
+    List { start: Option<u64>, items: Vec<Vec<Block>> },
+    Quote(Vec<Block>),
+    Table { header: Vec<Vec<Span>>, rows: Vec<Vec<Vec<Span>>> },
+    Rule,
+}
+
+pub fn parse(src: &str) -> Vec<Block> {
+    let opts = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let mut events = Parser::new_ext(src, opts);
+    blocks(&mut events, None)
+}
+
+type Events<'a> = Parser<'a>;
+
+fn blocks(events: &mut Events, end: Option<TagEnd>) -> Vec<Block> {
+    let mut out = Vec::new();
+    // Tight list items carry their text without a paragraph around it
+    let mut loose: Vec<Span> = Vec::new();
+    while let Some(ev) = events.next() {
+        let block = match ev {
+            Event::End(e) if Some(e) == end => break,
+            Event::Start(Tag::Paragraph) => Block::Paragraph(inlines(events, TagEnd::Paragraph, &Span::default())),
+            Event::Start(Tag::Heading { level, .. }) => {
+                Block::Heading(level as u8, inlines(events, TagEnd::Heading(level), &Span::default()))
+            }
+            Event::Start(Tag::BlockQuote(_)) => Block::Quote(blocks(events, Some(TagEnd::BlockQuote))),
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let lang = match kind {
+                    CodeBlockKind::Fenced(info) => info.split_whitespace().next().unwrap_or("").to_string(),
+                    CodeBlockKind::Indented => String::new(),
+                };
+                let mut code = String::new();
+                for ev in events.by_ref() {
+                    match ev {
+                        Event::Text(t) => code.push_str(&t),
+                        Event::End(TagEnd::CodeBlock) => break,
+                        _ => {}
+                    }
+                }
+                Block::Code {
+                    lang,
+                    code: code.trim_end_matches('\n').to_string(),
+                }
+            }
+            Event::Start(Tag::List(start)) => {
+                let mut items = Vec::new();
+                while let Some(ev) = events.next() {
+                    match ev {
+                        Event::Start(Tag::Item) => items.push(blocks(events, Some(TagEnd::Item))),
+                        Event::End(TagEnd::List(_)) => break,
+                        _ => {}
+                    }
+                }
+                Block::List { start, items }
+            }
+            Event::Start(Tag::Table(_)) => table(events),
+            Event::Start(Tag::HtmlBlock) => {
+                let mut text = String::new();
+                for ev in events.by_ref() {
+                    match ev {
+                        Event::Html(t) | Event::Text(t) => text.push_str(&t),
+                        Event::End(TagEnd::HtmlBlock) => break,
+                        _ => {}
+                    }
+                }
+                Block::Paragraph(vec![Span {
+                    text: text.trim_end().to_string(),
+                    ..Default::default()
+                }])
+            }
+            Event::Rule => Block::Rule,
+            ev => {
+                inline(ev, events, &Span::default(), &mut loose);
+                continue;
+            }
+        };
+        if !loose.is_empty() {
+            out.push(Block::Paragraph(std::mem::take(&mut loose)));
+        }
+        out.push(block);
+    }
+    if !loose.is_empty() {
+        out.push(Block::Paragraph(loose));
+    }
+    out
+}
+
+fn table(events: &mut Events) -> Block {
+    let mut header = Vec::new();
+    let mut rows: Vec<Vec<Vec<Span>>> = Vec::new();
+    let mut in_head = false;
+    while let Some(ev) = events.next() {
+        match ev {
+            Event::Start(Tag::TableHead) => in_head = true,
+            Event::End(TagEnd::TableHead) => in_head = false,
+            Event::Start(Tag::TableRow) => rows.push(Vec::new()),
+            Event::Start(Tag::TableCell) => {
+                let cell = inlines(events, TagEnd::TableCell, &Span::default());
+                match rows.last_mut() {
+                    Some(row) if !in_head => row.push(cell),
+                    _ => header.push(cell),
+                }
+            }
+            Event::End(TagEnd::Table) => break,
+            _ => {}
+        }
+    }
+    Block::Table { header, rows }
+}
+
+/// Inline content up to `end`, styled on top of `style`
+fn inlines(events: &mut Events, end: TagEnd, style: &Span) -> Vec<Span> {
+    let mut out = Vec::new();
+    while let Some(ev) = events.next() {
+        match ev {
+            Event::End(e) if e == end => break,
+            ev => inline(ev, events, style, &mut out),
+        }
+    }
+    out
+}
+
+fn inline(ev: Event, events: &mut Events, style: &Span, out: &mut Vec<Span>) {
+    let text = |t: &str| Span {
+        text: t.to_string(),
+        ..style.clone()
+    };
+    match ev {
+        Event::Text(t) | Event::InlineHtml(t) | Event::Html(t) => out.push(text(&t)),
+        Event::Code(t) | Event::InlineMath(t) | Event::DisplayMath(t) => out.push(Span {
+            code: true,
+            ..text(&t)
+        }),
+        Event::SoftBreak => out.push(text(" ")),
+        Event::HardBreak => out.push(text("\n")),
+        Event::TaskListMarker(done) => out.push(text(if done { "☑ " } else { "☐ " })),
+        Event::FootnoteReference(t) => out.push(text(&format!("[^{t}]"))),
+        Event::Start(Tag::Emphasis) => {
+            let s = Span { italic: true, ..style.clone() };
+            out.extend(inlines(events, TagEnd::Emphasis, &s));
+        }
+        Event::Start(Tag::Strong) => {
+            let s = Span { bold: true, ..style.clone() };
+            out.extend(inlines(events, TagEnd::Strong, &s));
+        }
+        Event::Start(Tag::Strikethrough) => {
+            let s = Span { strike: true, ..style.clone() };
+            out.extend(inlines(events, TagEnd::Strikethrough, &s));
+        }
+        Event::Start(Tag::Link { dest_url, .. }) => {
+            let s = Span {
+                link: Some(dest_url.to_string()),
+                ..style.clone()
+            };
+            out.extend(inlines(events, TagEnd::Link, &s));
+        }
+        // Images are shown as a link labelled with their alt text
+        Event::Start(Tag::Image { dest_url, .. }) => {
+            let s = Span {
+                link: Some(dest_url.to_string()),
+                ..style.clone()
+            };
+            let alt = inlines(events, TagEnd::Image, &s);
+            if alt.is_empty() {
+                out.push(Span { text: "🖼".into(), ..s });
+            } else {
+                out.extend(alt);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Byte offset after the last blank line that is not inside a code fence.
+/// Text before it will not change meaning as more tokens arrive.
+fn stable_boundary(src: &str) -> usize {
+    // Marker of the open fence; only the same kind closes it
+    let mut fence: Option<&str> = None;
+    let mut boundary = 0;
+    let mut offset = 0;
+    let mut prev_blank = false;
+    for line in src.split_inclusive('\n') {
+        let trimmed = line.trim();
+        // Only cut before text that starts a new top-level block
+        if prev_blank && fence.is_none() && !line.starts_with([' ', '\t']) {
+            boundary = offset;
+        }
+        if let Some(marker) = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m)) {
+            match fence {
+                None => fence = Some(marker),
+                Some(open) if open == marker => fence = None,
+                Some(_) => {}
+            }
+        }
+        prev_blank = fence.is_none() && trimmed.is_empty() && line.ends_with('\n');
+        offset += line.len();
+    }
+    boundary
+}
+
+struct CachedDoc {
+    source: String,
+    /// Length of `source` covered by `head`
+    head_len: usize,
+    head: Vec<Block>,
+    tail: Vec<Block>,
+}
+
+/// Parsed messages by index. A reply that is still streaming is split at
+/// its last stable blank line so only the growing tail is re-parsed; once
+/// it is complete it gets one full parse.
+#[derive(Default)]
+pub struct MarkdownCache {
+    docs: HashMap<usize, CachedDoc>,
+}
+
+impl MarkdownCache {
+    pub fn get(&mut self, idx: usize, src: &str, streaming: bool) -> (&[Block], &[Block]) {
+        let doc = self.docs.entry(idx).or_insert_with(|| CachedDoc {
+            source: String::new(),
+            head_len: 0,
+            head: vec![],
+            tail: vec![],
+        });
+        let split = doc.head_len != doc.source.len();
+        if doc.source != src || (split && !streaming) {
+            if streaming {
+                let cut = stable_boundary(src);
+                if cut != doc.head_len || src.get(..cut) != doc.source.get(..cut) {
+                    doc.head = parse(&src[..cut]);
+                    doc.head_len = cut;
+                }
+                doc.tail = parse(&src[cut..]);
+            } else {
+                doc.head = parse(src);
+                doc.head_len = src.len();
+                doc.tail.clear();
+            }
+            doc.source.clear();
+            doc.source.push_str(src);
+        }
+        (&doc.head, &doc.tail)
+    }
+
+    /// Drop entries past the end of a shortened chat
+    pub fn truncate(&mut self, len: usize) {
+        self.docs.retain(|i, _| *i < len);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn boundary_is_after_the_last_blank_line() {
+        assert_eq!(stable_boundary("one\n\ntwo"), 5);
+        assert_eq!(stable_boundary("one\n\ntwo\n\nthr"), 10);
+        assert_eq!(stable_boundary("one\ntwo"), 0);
+        // A blank line still being typed may be the start of a longer one
+        assert_eq!(stable_boundary("one\n\n"), 0);
+    }
+
+    #[test]
+    fn boundary_skips_blank_lines_inside_fences() {
+        let open = "intro\n\n```rust\nfn a() {}\n\nfn b() {}\n";
+        assert_eq!(stable_boundary(open), 7);
+        let closed = "intro\n\n```rust\nfn a() {}\n\n```\n\nafter";
+        assert_eq!(stable_boundary(closed), closed.len() - "after".len());
+    }
+
+    #[test]
+    fn boundary_handles_tilde_fences() {
+        let open = "intro\n\n~~~\na\n\nb\n";
+        assert_eq!(stable_boundary(open), 7);
+        // A backtick line does not close a tilde fence
+        let mixed = "intro\n\n~~~\n```\n\nb\n";
+        assert_eq!(stable_boundary(mixed), 7);
+        let closed = "~~~\na\n\n~~~\n\nafter";
+        assert_eq!(stable_boundary(closed), closed.len() - "after".len());
+    }
+
+    #[test]
+    fn boundary_keeps_indented_continuations_together() {
+        let list = "- item\n\n  more of the item\n";
+        assert_eq!(stable_boundary(list), 0);
+        let code = "text\n\n    indented code\n\n    more code";
+        assert_eq!(stable_boundary(code), 0);
+        let after = "text\n\n    indented code\n\nafter";
+        assert_eq!(stable_boundary(after), after.len() - "after".len());
+    }
+
+    #[test]
+    fn streaming_reuses_an_unchanged_head() {
+        let mut cache = MarkdownCache::default();
+        let (head, tail) = cache.get(0, "# Title\n\nfirst", true);
+        assert!(matches!(head, [Block::Heading(1, _)]));
+        assert!(matches!(tail, [Block::Paragraph(_)]));
+        let head_ptr = head.as_ptr();
+
+        let (head, tail) = cache.get(0, "# Title\n\nfirst words", true);
+        assert_eq!(head.as_ptr(), head_ptr);
+        assert!(matches!(tail, [Block::Paragraph(_)]));
+
+        // A new stable blank line moves the cut and re-parses the head
+        let (head, tail) = cache.get(0, "# Title\n\nfirst words\n\nsecond", true);
+        assert!(matches!(head, [Block::Heading(..), Block::Paragraph(_)]));
+        assert!(matches!(tail, [Block::Paragraph(_)]));
+    }
+
+    #[test]
+    fn finished_reply_is_parsed_whole() {
+        let mut cache = MarkdownCache::default();
+        let src = "a\n\n```\nx\n\ny\n```\n\nb";
+        cache.get(0, src, true);
+        let (head, tail) = cache.get(0, src, false);
+        assert!(tail.is_empty());
+        assert!(matches!(head, [Block::Paragraph(_), Block::Code { .. }, Block::Paragraph(_)]));
+        if let [_, Block::Code { code, .. }, _] = head {
+            assert_eq!(code, "x\n\ny");
+        }
+    }
+
+    #[test]
+    fn truncate_drops_later_messages() {
+        let mut cache = MarkdownCache::default();
+        cache.get(0, "a", false);
+        cache.get(1, "b", false);
+        cache.truncate(1);
+        assert_eq!(cache.docs.len(), 1);
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::mpsc,
+    time::Instant,
+};
+
+/// Samples kept per chart; one per second is five minutes
+const HISTORY_LEN: usize = 300;
+
+/// One poll of llama-server's monitoring endpoints
+pub struct ServerSample {
+    /// `Err` holds why `/health` is not ok, e.g. "Loading model"
+    pub health: Result<(), String>,
+    /// Prometheus values from `/metrics`; `Err` if the server was started
+    /// without `--metrics` or hides it behind an unknown key
+    pub metrics: Result<HashMap<String, f64>, String>,
+    pub busy_slots: Option<usize>,
+    pub total_slots: Option<usize>,
+    /// Context size summed over all slots
+    pub total_ctx: Option<u64>,
+}
+
+/// Values of a Prometheus text exposition, labels ignored
+pub fn parse_prometheus(text: &str) -> HashMap<String, f64> {
+    let mut out = HashMap::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (name, rest) = match line.find(['{', ' ']) {
+            Some(i) if line.as_bytes()[i] == b'{' => match line[i..].find('}') {
+                Some(end) => (&line[..i], &line[i + end + 1..]),
+                None => continue,
+            },
+            Some(i) => (&line[..i], &line[i..]),
+            None => continue,
+        };
+        if let Some(v) = rest.split_whitespace().next().and_then(|v| v.parse().ok()) {
+            out.insert(name.to_string(), v);
+        }
+    }
+    out
+}
+
+fn fetch_sample(client: &reqwest::blocking::Client, url: &str, api_key: Option<&str>) -> ServerSample {
+    let get = |path: &str| crate::connection::get_with(client, &format!("{url}{path}"), api_key).send();
+    let health = match get("/health") {
+        Ok(r) if r.status().is_success() => Ok(()),
+        Ok(r) => {
+            let status = r.status();
+            let body = r.json::<serde_json::Value>().unwrap_or_default();
+            Err(body["error"]["message"]
+                .as_str()
+                .map(str::to_string)
+                .unwrap_or_else(|| format!("HTTP {status}")))
+        }
+        Err(e) => Err(e.to_string()),
+    };
+    let metrics = match get("/metrics") {
+        Ok(r) if r.status().is_success() => r.text().map(|t| parse_prometheus(&t)).map_err(|e| e.to_string()),
+        Ok(r) => Err(format!("HTTP {}", r.status())),
+        Err(e) => Err(e.to_string()),
+    };
+    let slots = get("/slots")
+        .ok()
+        .filter(|r| r.status().is_success())
+        .and_then(|r| r.json::<Vec<serde_json::Value>>().ok());
+    ServerSample {
+        health,
+        metrics,
+        // Older builds report a numeric `state` instead of `is_processing`
+        busy_slots: slots.as_ref().map(|s| {
+            s.iter()
+                .filter(|slot| slot["is_processing"].as_bool().unwrap_or(slot["state"].as_u64().unwrap_or(0) != 0))
+                .count()
+        }),
+        total_slots: slots.as_ref().map(|s| s.len()),
+        total_ctx: slots.as_ref().map(|s| s.iter().filter_map(|slot| slot["n_ctx"].as_u64()).sum()),
+    }
+}
+
+/// `client` is shared by every poll; cloning it keeps one connection pool
+pub fn spawn_sample(client: reqwest::blocking::Client, url: String, api_key: Option<String>, tx: mpsc::Sender<ServerSample>) {
+    std::thread::spawn(move || {
+        let _ = tx.send(fetch_sample(&client, &url, api_key.as_deref()));
+    });
+}
+
+/// One point on the dashboard charts
+#[derive(Clone, Copy)]
+pub struct PerfPoint {
+    /// Seconds since the dashboard started sampling
+    pub t: f64,
+    /// Average speed of the requests that finished since the previous
+    /// sample; llama-server only accounts a request once it ends, so this
+    /// is `None` while nothing finished
+    pub prompt_tps: Option<f64>,
+    pub gen_tps: Option<f64>,
+    /// Fraction of the KV cache in use, if the build reports it
+    pub kv_used: Option<f64>,
+    pub busy_slots: f64,
+}
+
+/// Rolling history built from successive samples
+pub struct PerfHistory {
+    start: Instant,
+    prev: Option<HashMap<String, f64>>,
+    pub points: VecDeque<PerfPoint>,
+    pub last: Option<ServerSample>,
+}
+
+impl Default for PerfHistory {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            prev: None,
+            points: VecDeque::new(),
+            last: None,
+        }
+    }
+}
+
+/// Throughput gauges, reset by llama-server on every `/metrics` read
+const PROMPT_GAUGE: &str = "llamacpp:prompt_tokens_seconds";
+const GEN_GAUGE: &str = "llamacpp:predicted_tokens_seconds";
+const PROMPT_TOKENS: &str = "llamacpp:prompt_tokens_total";
+const PROMPT_SECONDS: &str = "llamacpp:prompt_seconds_total";
+const GEN_TOKENS: &str = "llamacpp:tokens_predicted_total";
+const GEN_SECONDS: &str = "llamacpp:tokens_predicted_seconds_total";
+
+/// Tokens per second of requests finished between two samples: the
+/// server's gauge if it has one, else the change in its counters
+fn rate(prev: Option<&HashMap<String, f64>>, now: &HashMap<String, f64>, gauge: &str, tokens: &str, seconds: &str) -> Option<f64> {
+    if let Some(v) = now.get(gauge) {
+        return Some(*v).filter(|v| *v > 0.0 && v.is_finite());
+    }
+    let prev = prev?;
+    let delta = |k: &str| Some(now.get(k)? - prev.get(k)?);
+    match (delta(tokens), delta(seconds)) {
+        // Counters go backwards when the server restarts
+        (Some(n), Some(s)) if n > 0.0 && s > 0.0 => Some(n / s),
+        _ => None,
+    }
+}
+
+impl PerfHistory {
+    pub fn push(&mut self, sample: ServerSample) {
+        let mut point = PerfPoint {
+            t: self.start.elapsed().as_secs_f64(),
+            prompt_tps: None,
+            gen_tps: None,
+            kv_used: None,
+            busy_slots: sample.busy_slots.unwrap_or(0) as f64,
+        };
+        if let Ok(m) = &sample.metrics {
+            point.prompt_tps = rate(self.prev.as_ref(), m, PROMPT_GAUGE, PROMPT_TOKENS, PROMPT_SECONDS);
+            point.gen_tps = rate(self.prev.as_ref(), m, GEN_GAUGE, GEN_TOKENS, GEN_SECONDS);
+            point.kv_used = m.get("llamacpp:kv_cache_usage_ratio").copied().or_else(|| {
+                let tokens = m.get("llamacpp:kv_cache_tokens")?;
+                let ctx = sample.total_ctx.filter(|c| *c > 0)?;
+                Some(tokens / ctx as f64)
+            });
+            if sample.busy_slots.is_none() {
+                point.busy_slots = m.get("llamacpp:requests_processing").copied().unwrap_or(0.0);
+            }
+            self.prev = Some(m.clone());
+        }
+        self.points.push_back(point);
+        while self.points.len() > HISTORY_LEN {
+            self.points.pop_front();
+        }
+        self.last = Some(sample);
+    }
+
+    pub fn clear(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_unlabeled_values() {
+        let m = parse_prometheus(
+            "# HELP llamacpp:prompt_tokens_total Number of prompt tokens processed.\n\
+             # TYPE llamacpp:prompt_tokens_total counter\n\
+             llamacpp:prompt_tokens_total 1234\n\
+             llamacpp:kv_cache_usage_ratio 0.25\n\
+             llamacpp:predicted_tokens_seconds 41.5 1700000000\n",
+        );
+        assert_eq!(m.len(), 3);
+        assert_eq!(m["llamacpp:prompt_tokens_total"], 1234.0);
+        assert_eq!(m["llamacpp:kv_cache_usage_ratio"], 0.25);
+        assert_eq!(m["llamacpp:predicted_tokens_seconds"], 41.5);
+    }
+
+    #[test]
+    fn parses_labeled_values() {
+        let m = parse_prometheus(
+            "llamacpp:requests_processing{model=\"qwen\",slot=\"0\"} 2\n\
+             http_requests_total{path=\"/a b\"}   7\n",
+        );
+        assert_eq!(m["llamacpp:requests_processing"], 2.0);
+        assert_eq!(m["http_requests_total"], 7.0);
+    }
+
+    #[test]
+    fn skips_malformed_lines() {
+        let m = parse_prometheus("no_value\nbroken{label=\"x\" 3\nnot_a_number abc\n\n   \nok 1\n");
+        assert_eq!(m.len(), 1);
+        assert_eq!(m["ok"], 1.0);
+    }
+
+    #[test]
+    fn rate_prefers_the_gauge_and_falls_back_to_counters() {
+        let map = |pairs: &[(&str, f64)]| pairs.iter().map(|(k, v)| (k.to_string(), *v)).collect::<HashMap<_, _>>();
+        let prev = map(&[(GEN_TOKENS, 100.0), (GEN_SECONDS, 10.0)]);
+        let now = map(&[(GEN_TOKENS, 150.0), (GEN_SECONDS, 12.0)]);
+        assert_eq!(rate(Some(&prev), &now, GEN_GAUGE, GEN_TOKENS, GEN_SECONDS), Some(25.0));
+        assert_eq!(rate(None, &now, GEN_GAUGE, GEN_TOKENS, GEN_SECONDS), None);
+        assert_eq!(rate(Some(&now), &now, GEN_GAUGE, GEN_TOKENS, GEN_SECONDS), None);
+
+        let gauge = map(&[(GEN_GAUGE, 30.0), (GEN_TOKENS, 150.0), (GEN_SECONDS, 12.0)]);
+        assert_eq!(rate(Some(&prev), &gauge, GEN_GAUGE, GEN_TOKENS, GEN_SECONDS), Some(30.0));
+        let idle = map(&[(GEN_GAUGE, 0.0)]);
+        assert_eq!(rate(Some(&prev), &idle, GEN_GAUGE, GEN_TOKENS, GEN_SECONDS), None);
+    }
+}
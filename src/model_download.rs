@@ -1,72 +1,316 @@
-use crate::models::{DownloadEvent, DownloadKind};
+use crate::models::{DownloadEvent, DownloadKind, HFFile, JobControl, JobId};
+use sha2::{Digest, Sha256};
 use std::{
     fs,
     io::{Read, Write},
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::mpsc,
+    time::Duration,
 };
 
-pub fn spawn_model_download(url: String, dest: PathBuf, tx: mpsc::Sender<DownloadEvent>) {
-    std::thread::spawn(move || {
-        let client = reqwest::blocking::Client::new();
-        let r = client.get(&url).send();
-        let mut resp = match r {
-            Ok(r) => r,
-            Err(e) => {
-                let _ = tx.send(DownloadEvent::Error {
-                    kind: DownloadKind::Model,
-                    err: e.to_string(),
-                });
-                return;
+const MAX_ATTEMPTS: u32 = 5;
+
+/// In-progress downloads live next to the final file with a `.part` suffix
+pub fn part_path(dest: &Path) -> PathBuf {
+    let mut name = dest.file_name().unwrap_or_default().to_os_string();
+    name.push(".part");
+    dest.with_file_name(name)
+}
+
+/// Reports progress of one file as part of the whole job, so shard sets show
+/// a single bar running across every part
+struct Reporter<'a> {
+    job: JobId,
+    tx: &'a mpsc::Sender<DownloadEvent>,
+    /// Bytes of earlier files in the job
+    base: u64,
+    overall: Option<u64>,
+}
+
+impl Reporter<'_> {
+    fn progress(&self, current: u64, file_total: Option<u64>, stage: &'static str) {
+        let _ = self.tx.send(DownloadEvent::Progress {
+            job: self.job,
+            kind: DownloadKind::Model,
+            current: self.base + current,
+            total: self.overall.or(file_total.map(|t| self.base + t)),
+            stage,
+        });
+    }
+}
+
+fn send_err(tx: &mpsc::Sender<DownloadEvent>, job: JobId, err: String) {
+    let _ = tx.send(DownloadEvent::Error {
+        job,
+        kind: DownloadKind::Model,
+        err,
+    });
+}
+
+enum Attempt {
+    Complete,
+    /// Transient failure; the `.part` file is kept for the next attempt
+    Retry(String),
+    Fatal(String),
+    /// Paused or cancelled by the user
+    Stopped,
+}
+
+/// Fetch `url` into `part`, appending to whatever is already there
+fn download_attempt(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    part: &Path,
+    expected: Option<u64>,
+    control: &JobControl,
+    report: &Reporter,
+) -> Attempt {
+    let mut offset = fs::metadata(part).map(|m| m.len()).unwrap_or(0);
+    if expected.is_some_and(|e| offset >= e) {
+        return Attempt::Complete;
+    }
+    let mut req = client.get(url);
+    if offset > 0 {
+        req = req.header(reqwest::header::RANGE, format!("bytes={offset}-"));
+    }
+    let mut resp = match req.send() {
+        Ok(r) => r,
+        Err(e) => return Attempt::Retry(e.to_string()),
+    };
+    let status = resp.status();
+    if status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE {
+        // Nothing left to fetch; verification decides if the file is good
+        return Attempt::Complete;
+    }
+    if !status.is_success() {
+        let msg = format!("HTTP {status}");
+        return if status.is_server_error() {
+            Attempt::Retry(msg)
+        } else {
+            Attempt::Fatal(msg)
+        };
+    }
+    let resumed = status == reqwest::StatusCode::PARTIAL_CONTENT;
+    if !resumed {
+        // Server ignored the range request; start over
+        offset = 0;
+    }
+    let total = expected.or_else(|| resp.content_length().map(|l| l + offset));
+    let file = fs::OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(resumed)
+        .truncate(!resumed)
+        .open(part);
+    let mut file = match file {
+        Ok(f) => f,
+        Err(e) => return Attempt::Fatal(e.to_string()),
+    };
+    let mut buf = [0u8; 64 * 1024];
+    let mut downloaded = offset;
+    loop {
+        if control.should_stop() {
+            let _ = file.sync_all();
+            return Attempt::Stopped;
+        }
+        match resp.read(&mut buf) {
+            Ok(0) => break,
+            Ok(n) => {
+                if let Err(e) = file.write_all(&buf[..n]) {
+                    return Attempt::Fatal(e.to_string());
+                }
+                downloaded += n as u64;
+                report.progress(downloaded, total, "download");
             }
+            Err(e) => return Attempt::Retry(e.to_string()),
+        }
+    }
+    if let Err(e) = file.sync_all() {
+        return Attempt::Fatal(e.to_string());
+    }
+    Attempt::Complete
+}
+
+/// Hash of the file, or `None` if the job was stopped while hashing
+fn sha256_file(path: &Path, control: &JobControl, report: &Reporter) -> anyhow::Result<Option<String>> {
+    let total = fs::metadata(path)?.len();
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buf = [0u8; 256 * 1024];
+    let mut done: u64 = 0;
+    loop {
+        if control.should_stop() {
+            return Ok(None);
+        }
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buf[..n]);
+        done += n as u64;
+        report.progress(done, Some(total), "verify");
+    }
+    Ok(Some(
+        hasher
+            .finalize()
+            .iter()
+            .map(|b| format!("{b:02x}"))
+            .collect(),
+    ))
+}
+
+/// Check the finished `.part` file against the Hub metadata. `Ok(false)`
+/// means the job was stopped before the check finished.
+fn verify(part: &Path, meta: Option<&HFFile>, control: &JobControl, report: &Reporter) -> anyhow::Result<bool> {
+    let Some(meta) = meta else {
+        return Ok(true);
+    };
+    let len = fs::metadata(part)?.len();
+    if let Some(expected) = meta.expected_size()
+        && len != expected
+    {
+        anyhow::bail!("size mismatch: got {len} bytes, expected {expected}");
+    }
+    if let Some(lfs) = &meta.lfs {
+        let Some(got) = sha256_file(part, control, report)? else {
+            return Ok(false);
         };
-        let total = resp.content_length();
-        if let Some(p) = dest.parent() {
-            let _ = fs::create_dir_all(p);
+        if !got.eq_ignore_ascii_case(&lfs.sha256) {
+            anyhow::bail!("SHA256 mismatch: got {got}, expected {}", lfs.sha256);
+        }
+    }
+    Ok(true)
+}
+
+/// Fetch one file of the job. `Ok(None)` means the user stopped the job.
+fn download_file(
+    client: &reqwest::blocking::Client,
+    url: &str,
+    dest: &Path,
+    meta: Option<&HFFile>,
+    control: &JobControl,
+    report: &Reporter,
+) -> Result<Option<u64>, String> {
+    if let Some(p) = dest.parent() {
+        let _ = fs::create_dir_all(p);
+    }
+    let expected = meta.and_then(|m| m.expected_size());
+    let part = part_path(dest);
+    let mut attempt = 0;
+    loop {
+        match download_attempt(client, url, &part, expected, control, report) {
+            Attempt::Complete => break,
+            Attempt::Fatal(e) => return Err(e),
+            Attempt::Stopped => return Ok(None),
+            Attempt::Retry(e) => {
+                attempt += 1;
+                if attempt >= MAX_ATTEMPTS {
+                    return Err(format!("{e} (partial download kept, retry to resume)"));
+                }
+                let have = fs::metadata(&part).map(|m| m.len()).unwrap_or(0);
+                report.progress(have, expected, "retrying");
+                std::thread::sleep(Duration::from_secs(2u64.pow(attempt)));
+            }
+        }
+    }
+
+    match verify(&part, meta, control, report) {
+        Ok(true) => {}
+        Ok(false) => return Ok(None),
+        Err(e) => {
+            let _ = fs::remove_file(&part);
+            return Err(e.to_string());
+        }
+    }
+    // A stop that arrived during the last read of the hash still counts
+    if control.should_stop() {
+        return Ok(None);
+    }
+    let len = fs::metadata(&part).map(|m| m.len()).unwrap_or(0);
+    fs::rename(&part, dest).map_err(|e| e.to_string())?;
+    Ok(Some(len))
+}
+
+/// The repo's file list with the sizes and hashes downloads are checked
+/// against, retried like the downloads themselves. `Ok(None)` means the user
+/// stopped the job.
+fn fetch_listing(repo: &str, control: &JobControl) -> Result<Option<Vec<HFFile>>, String> {
+    let mut attempt = 0;
+    loop {
+        if control.should_stop() {
+            return Ok(None);
         }
-        let mut file = match fs::File::create(&dest) {
-            Ok(f) => f,
+        match crate::hf::hf_fetch_files(repo) {
+            Ok(listing) => return Ok(Some(listing)),
             Err(e) => {
-                let _ = tx.send(DownloadEvent::Error {
-                    kind: DownloadKind::Model,
-                    err: e.to_string(),
-                });
+                attempt += 1;
+                if attempt >= MAX_ATTEMPTS {
+                    return Err(format!("could not list the repo's files to verify them: {e}"));
+                }
+                std::thread::sleep(Duration::from_secs(2u64.pow(attempt)));
+            }
+        }
+    }
+}
+
+/// Download `files` (repo path, destination) from `repo`; a split model
+/// passes all of its shards here so they arrive as one job. Each file goes to
+/// a `.part` file that is resumed with Range requests and only renamed into
+/// place once its size and SHA256 match the Hub's metadata. Stopping through
+/// `control` keeps the `.part` file so a later job can resume it.
+pub fn spawn_model_download(
+    job: JobId,
+    repo: String,
+    files: Vec<(String, PathBuf)>,
+    control: JobControl,
+    tx: mpsc::Sender<DownloadEvent>,
+) {
+    std::thread::spawn(move || {
+        let listing = match fetch_listing(&repo, &control) {
+            Ok(Some(listing)) => listing,
+            Ok(None) => {
+                let _ = tx.send(DownloadEvent::Stopped { job });
                 return;
             }
+            Err(e) => return send_err(&tx, job, e),
         };
-        let mut buf = [0u8; 64 * 1024];
-        let mut downloaded: u64 = 0;
-        loop {
-            match resp.read(&mut buf) {
-                Ok(0) => break,
-                Ok(n) => {
-                    if let Err(e) = file.write_all(&buf[..n]) {
-                        let _ = tx.send(DownloadEvent::Error {
-                            kind: DownloadKind::Model,
-                            err: e.to_string(),
-                        });
-                        return;
-                    }
-                    downloaded += n as u64;
-                    let _ = tx.send(DownloadEvent::Progress {
-                        kind: DownloadKind::Model,
-                        current: downloaded,
-                        total,
-                        stage: "download",
-                    });
+        let metas: Vec<Option<&HFFile>> = files
+            .iter()
+            .map(|(file, _)| listing.iter().find(|f| f.rfilename == *file))
+            .collect();
+        let overall: Option<u64> = metas.iter().map(|m| m.and_then(|m| m.expected_size())).sum();
+        let client = reqwest::blocking::Client::new();
+
+        let mut base = 0;
+        for ((file, dest), meta) in files.iter().zip(metas) {
+            if dest.exists() {
+                base += fs::metadata(dest).map(|m| m.len()).unwrap_or(0);
+                continue;
+            }
+            let report = Reporter {
+                job,
+                tx: &tx,
+                base,
+                overall,
+            };
+            let url = crate::hf::hf_resolve_url(&repo, file);
+            match download_file(&client, &url, dest, meta, &control, &report) {
+                Ok(Some(len)) => base += len,
+                Ok(None) => {
+                    let _ = tx.send(DownloadEvent::Stopped { job });
+                    return;
                 }
                 Err(e) => {
-                    let _ = tx.send(DownloadEvent::Error {
-                        kind: DownloadKind::Model,
-                        err: e.to_string(),
-                    });
-                    return;
+                    let e = if files.len() > 1 { format!("{file}: {e}") } else { e };
+                    return send_err(&tx, job, e);
                 }
             }
         }
+
         let _ = tx.send(DownloadEvent::Done {
+            job,
             kind: DownloadKind::Model,
-            dest: Some(dest),
+            dest: files.first().map(|(_, d)| d.clone()),
         });
     });
 }
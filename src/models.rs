@@ -1,5 +1,5 @@
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::{collections::HashMap, path::PathBuf};
 
 #[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum Backend {
@@ -17,25 +17,319 @@ pub enum DownloadKind {
     Model,
 }
 
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SamplingParams {
+    pub temperature: f32,
+    pub max_tokens: i32,
+    pub top_p: f32,
+    pub top_k: i32,
+    pub min_p: f32,
+    pub repeat_penalty: f32,
+    pub presence_penalty: f32,
+    pub frequency_penalty: f32,
+    /// -1 lets llama-server pick a random seed
+    pub seed: i64,
+    pub stop: Vec<String>,
+    /// 0 = off, 1 = Mirostat, 2 = Mirostat 2.0
+    pub mirostat: u8,
+    pub mirostat_tau: f32,
+    pub mirostat_eta: f32,
+}
+
+impl Default for SamplingParams {
+    fn default() -> Self {
+        Self {
+            temperature: 0.7,
+            max_tokens: 1024,
+            top_p: 0.95,
+            top_k: 40,
+            min_p: 0.05,
+            repeat_penalty: 1.1,
+            presence_penalty: 0.0,
+            frequency_penalty: 0.0,
+            seed: -1,
+            stop: vec![],
+            mirostat: 0,
+            mirostat_tau: 5.0,
+            mirostat_eta: 0.1,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct SamplingPreset {
+    pub name: String,
+    #[serde(flatten)]
+    pub params: SamplingParams,
+}
+
+pub fn default_sampling_presets() -> Vec<SamplingPreset> {
+    let base = SamplingParams::default();
+    vec![
+        SamplingPreset {
+            name: "Balanced".into(),
+            params: base.clone(),
+        },
+        SamplingPreset {
+            name: "Precise".into(),
+            params: SamplingParams {
+                temperature: 0.2,
+                top_p: 0.9,
+                ..base.clone()
+            },
+        },
+        SamplingPreset {
+            name: "Creative".into(),
+            params: SamplingParams {
+                temperature: 1.0,
+                top_p: 0.98,
+                min_p: 0.02,
+                ..base.clone()
+            },
+        },
+        SamplingPreset {
+            name: "Deterministic".into(),
+            params: SamplingParams {
+                temperature: 0.0,
+                top_k: 1,
+                seed: 42,
+                ..base
+            },
+        },
+    ]
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Persona {
+    pub name: String,
+    pub system_prompt: String,
+    /// Sampling preset applied when this persona is picked
+    #[serde(default)]
+    pub preset: Option<String>,
+}
+
+pub fn default_personas() -> Vec<Persona> {
+    vec![
+        Persona {
+            name: "Assistant".into(),
+            system_prompt: "You are a helpful assistant.".into(),
+            preset: None,
+        },
+        Persona {
+            name: "Coder".into(),
+            system_prompt: "You are an expert programmer. Answer with concise explanations and complete, working code.".into(),
+            preset: Some("Precise".into()),
+        },
+    ]
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LaunchProfile {
+    pub name: String,
+    /// 0 uses the context length stored in the model
+    pub ctx_size: u32,
+    /// -1 lets llama-server decide
+    pub threads: i32,
+    pub batch_size: u32,
+    /// -1 offloads everything on GPU backends and nothing on CPU
+    pub gpu_layers: i32,
+    pub flash_attn: bool,
+    pub mlock: bool,
+    pub mmap: bool,
+    pub cache_type_k: String,
+    pub cache_type_v: String,
+    pub parallel: u32,
+    pub extra_args: String,
+}
+
+impl Default for LaunchProfile {
+    fn default() -> Self {
+        Self {
+            name: "Default".into(),
+            ctx_size: 4096,
+            threads: -1,
+            batch_size: 2048,
+            gpu_layers: -1,
+            flash_attn: false,
+            mlock: false,
+            mmap: true,
+            cache_type_k: "f16".into(),
+            cache_type_v: "f16".into(),
+            parallel: 1,
+            extra_args: String::new(),
+        }
+    }
+}
+
+pub const KV_CACHE_TYPES: &[&str] = &["f32", "f16", "bf16", "q8_0", "q4_0", "q4_1", "iq4_nl", "q5_0", "q5_1"];
+
+/// Kind of server a connection talks to
+#[derive(Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum ConnectionKind {
+    /// The llama-server this app starts and supervises
+    LocalManaged,
+    LlamaServer,
+    Ollama,
+    Vllm,
+    LmStudio,
+    OpenAi,
+}
+
+impl ConnectionKind {
+    pub const ALL: [ConnectionKind; 6] = [
+        ConnectionKind::LocalManaged,
+        ConnectionKind::LlamaServer,
+        ConnectionKind::Ollama,
+        ConnectionKind::Vllm,
+        ConnectionKind::LmStudio,
+        ConnectionKind::OpenAi,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ConnectionKind::LocalManaged => "Local (managed)",
+            ConnectionKind::LlamaServer => "Remote llama-server",
+            ConnectionKind::Ollama => "Ollama",
+            ConnectionKind::Vllm => "vLLM",
+            ConnectionKind::LmStudio => "LM Studio",
+            ConnectionKind::OpenAi => "OpenAI-compatible",
+        }
+    }
+
+    pub fn is_llama_server(self) -> bool {
+        matches!(self, ConnectionKind::LocalManaged | ConnectionKind::LlamaServer)
+    }
+
+    /// llama-server continues a trailing assistant message instead of
+    /// answering it; other servers may start a new reply
+    pub fn supports_prefill(self) -> bool {
+        self.is_llama_server()
+    }
+
+    pub fn default_url(self) -> &'static str {
+        match self {
+            ConnectionKind::LocalManaged => "",
+            ConnectionKind::LlamaServer => "http://127.0.0.1:8080",
+            ConnectionKind::Ollama => "http://127.0.0.1:11434",
+            ConnectionKind::Vllm => "http://127.0.0.1:8000",
+            ConnectionKind::LmStudio => "http://127.0.0.1:1234",
+            ConnectionKind::OpenAi => "https://api.openai.com",
+        }
+    }
+}
+
+/// A named chat endpoint speaking the OpenAI API
 #[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Connection {
+    pub name: String,
+    pub kind: ConnectionKind,
+    /// Base URL without `/v1`; unused for the managed server
+    pub url: String,
+    /// Sent as a bearer token when not empty
+    pub api_key: String,
+    /// Model id sent with requests; empty lets the server choose
+    pub model: String,
+}
+
+impl Default for Connection {
+    fn default() -> Self {
+        Self {
+            name: "Local".to_string(),
+            kind: ConnectionKind::LocalManaged,
+            url: String::new(),
+            api_key: String::new(),
+            model: String::new(),
+        }
+    }
+}
+
+/// One entry of an OpenAI `/v1/models` response
+#[derive(Clone, PartialEq, Deserialize)]
+pub struct ModelEntry {
+    pub id: String,
+    #[serde(default)]
+    pub owned_by: Option<String>,
+    #[serde(default)]
+    pub created: Option<i64>,
+}
+
+#[derive(Deserialize)]
+pub struct ModelList {
+    pub data: Vec<ModelEntry>,
+}
+
+/// Result of the last `/v1/models` probe of a connection
+#[derive(Clone, PartialEq)]
+pub enum ConnectionHealth {
+    Checking,
+    /// Reachable, serving this many models
+    Online(usize),
+    Offline(String),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
     pub default_runtime: Option<String>,
+    /// Keep using `default_runtime` when newer builds get installed
+    pub runtime_pinned: bool,
+    /// Extra llama-server binaries or directories to look in
+    pub custom_runtime_paths: Vec<PathBuf>,
+    /// Hours between llama.cpp release checks; 0 turns them off
+    pub update_check_hours: u32,
+    /// Unix time of the last release check
+    pub last_update_check: u64,
     pub auto_start_server: bool,
     pub auto_stop_server: bool,
     pub server_timeout_minutes: u32,
+    /// 0 picks a free port automatically
+    pub server_port: u16,
+    pub max_concurrent_downloads: usize,
+    pub auto_restart_server: bool,
+    pub max_restart_attempts: u32,
     pub theme: String,
     pub max_chat_history: usize,
+    pub sampling_presets: Vec<SamplingPreset>,
+    pub default_preset: String,
+    pub personas: Vec<Persona>,
+    pub default_persona: Option<String>,
+    pub launch_profiles: Vec<LaunchProfile>,
+    pub default_launch_profile: String,
+    /// Model file name -> launch profile name
+    pub model_profiles: HashMap<String, String>,
+    pub connections: Vec<Connection>,
+    pub default_connection: String,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
             default_runtime: None,
+            runtime_pinned: false,
+            custom_runtime_paths: vec![],
+            update_check_hours: 24,
+            last_update_check: 0,
             auto_start_server: true,
             auto_stop_server: true,
             server_timeout_minutes: 30,
+            server_port: 0,
+            max_concurrent_downloads: 2,
+            auto_restart_server: true,
+            max_restart_attempts: 3,
             theme: "dark".to_string(),
             max_chat_history: 1000,
+            sampling_presets: default_sampling_presets(),
+            default_preset: "Balanced".to_string(),
+            personas: default_personas(),
+            default_persona: None,
+            launch_profiles: vec![LaunchProfile::default()],
+            default_launch_profile: "Default".to_string(),
+            model_profiles: HashMap::new(),
+            connections: vec![Connection::default()],
+            default_connection: "Local".to_string(),
         }
     }
 }
@@ -46,45 +340,237 @@ pub struct RuntimeInfo {
     pub path: PathBuf,
     pub version: String,
     pub backend: Backend,
+    /// Install directory, e.g. `runtimes/b1234-vulkan`
+    #[serde(default)]
+    pub dir: PathBuf,
+    #[serde(default)]
+    pub source: RuntimeSource,
+    /// Compiler and target line reported by `--version`
+    #[serde(default)]
+    pub build_info: String,
+}
+
+/// Where a runtime was found
+#[derive(Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum RuntimeSource {
+    /// Downloaded and owned by the app
+    #[default]
+    Managed,
+    /// Found on `PATH`
+    Path,
+    /// Found in a well-known install location
+    System,
+    /// Added by the user in settings
+    Custom,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
 pub enum ServerStatus {
     Stopped,
     Starting,
+    /// Restarting llama-server with a different model
+    Switching(String),
     Running,
     Error(String),
 }
 
+/// Known reasons for llama-server to die during startup
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ServerFault {
+    OutOfMemory,
+    BadModelFile,
+    UnsupportedArch,
+    PortInUse,
+}
+
+impl ServerFault {
+    pub fn describe(&self, port: u16) -> String {
+        match self {
+            ServerFault::OutOfMemory => "out of memory while loading the model; try fewer GPU layers or a smaller context".into(),
+            ServerFault::BadModelFile => "the model file is invalid or corrupted".into(),
+            ServerFault::UnsupportedArch => "this llama.cpp build does not support the model architecture".into(),
+            ServerFault::PortInUse => format!("could not bind port {port}"),
+        }
+    }
+}
+
+pub type JobId = u64;
+
 #[derive(Debug)]
 pub enum DownloadEvent {
     Progress {
+        job: JobId,
         kind: DownloadKind,
         current: u64,
         total: Option<u64>,
         stage: &'static str,
     },
     Done {
+        job: JobId,
         kind: DownloadKind,
         dest: Option<PathBuf>,
     },
     Error {
+        job: JobId,
         kind: DownloadKind,
         err: String,
     },
+    /// The worker stopped because the job was paused or cancelled
+    Stopped { job: JobId },
+}
+
+impl DownloadEvent {
+    pub fn job(&self) -> JobId {
+        match self {
+            DownloadEvent::Progress { job, .. }
+            | DownloadEvent::Done { job, .. }
+            | DownloadEvent::Error { job, .. }
+            | DownloadEvent::Stopped { job, .. } => *job,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum JobState {
+    Queued,
+    Running,
+    Paused,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// Everything needed to (re)start a download job
+#[derive(Clone, Debug)]
+pub enum JobSpec {
+    /// One file, or every shard of a split model (repo path, destination)
+    Model {
+        repo: String,
+        files: Vec<(String, PathBuf)>,
+    },
+    Runtime {
+        url: String,
+        zip_path: PathBuf,
+        bin_dir: PathBuf,
+    },
+}
+
+/// Shared flag a worker polls between chunks to learn it should stop
+#[derive(Clone, Default)]
+pub struct JobControl(std::sync::Arc<std::sync::atomic::AtomicBool>);
+
+impl JobControl {
+    pub fn stop(&self) {
+        self.0.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn should_stop(&self) -> bool {
+        self.0.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+pub struct DownloadJob {
+    pub id: JobId,
+    pub kind: DownloadKind,
+    pub label: String,
+    pub spec: JobSpec,
+    pub state: JobState,
+    pub current: u64,
+    pub total: Option<u64>,
+    pub stage: &'static str,
+    pub error: Option<String>,
+    /// Smoothed bytes per second
+    pub speed: f64,
+    pub sample: (std::time::Instant, u64),
+    pub control: JobControl,
 }
 
 #[derive(Debug)]
 pub enum StreamEvent {
     Token(String),
+    /// `finish_reason` of the last choice
+    Finish(FinishReason),
+    Usage(Usage),
+    Timings(Timings),
     Done,
     Error(String),
 }
 
+/// OpenAI-style token counts sent with the last chunk
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Usage {
+    #[serde(default)]
+    pub prompt_tokens: u64,
+    #[serde(default)]
+    pub completion_tokens: u64,
+}
+
+/// llama-server's per-request `timings` object
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Timings {
+    /// Prompt tokens reused from the slot's cache
+    #[serde(default)]
+    pub cache_n: u64,
+    #[serde(default)]
+    pub prompt_n: u64,
+    #[serde(default)]
+    pub prompt_ms: f64,
+    #[serde(default)]
+    pub prompt_per_second: f64,
+    #[serde(default)]
+    pub predicted_n: u64,
+    #[serde(default)]
+    pub predicted_ms: f64,
+    #[serde(default)]
+    pub predicted_per_second: f64,
+}
+
+/// Why a reply stopped growing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FinishReason {
+    Stop,
+    /// Hit `max_tokens` or the context size
+    Length,
+    Cancel,
+    Error,
+}
+
 #[derive(Default, Clone, Serialize, Deserialize)]
 pub struct Msg {
     pub role: String,
     pub content: String,
+    /// Model that produced an assistant reply; never sent to the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Why an assistant reply ended; never sent to the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finish: Option<FinishReason>,
+    /// Stats reported with the reply; never sent to the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timings: Option<Timings>,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Conversation {
+    pub id: String,
+    pub title: String,
+    pub created_at: u64,
+    pub updated_at: u64,
+    pub model: Option<String>,
+    #[serde(default)]
+    pub preset: Option<String>,
+    #[serde(default)]
+    pub persona: Option<String>,
+    #[serde(default)]
+    pub connection: Option<String>,
+    /// The branch that was showing
+    pub msgs: Vec<Msg>,
+    /// All branches; empty for conversations saved before branching
+    #[serde(default)]
+    pub tree: crate::tree::ChatTree,
 }
 
 #[derive(Deserialize)]
@@ -101,31 +587,199 @@ pub struct HFModelInfo {
 pub struct HFFile {
     pub rfilename: String,
     pub size: Option<u64>,
+    #[serde(default)]
+    pub lfs: Option<HFLfs>,
+}
+
+impl HFFile {
+    pub fn expected_size(&self) -> Option<u64> {
+        self.lfs.as_ref().map(|l| l.size).or(self.size)
+    }
+}
+
+#[derive(Deserialize, Clone)]
+pub struct HFLfs {
+    pub sha256: String,
+    pub size: u64,
 }
 
 #[derive(Clone)]
 pub struct DownloadedModel {
+    /// File passed to llama-server; the first shard for split models
     pub file_name: String,
     pub path: PathBuf,
+    /// Combined size of all parts
     pub size: Option<u64>,
+    /// Every file of the model, in shard order
+    pub parts: Vec<PathBuf>,
+    /// Number of shards the set should have (1 for single files)
+    pub expected_parts: u32,
+    /// Header metadata, or why it could not be read
+    pub info: Result<std::sync::Arc<GgufInfo>, String>,
 }
 
-#[derive(Deserialize)]
+/// Metadata read from a GGUF header
+#[derive(Clone, Default)]
+pub struct GgufInfo {
+    pub version: u32,
+    pub name: Option<String>,
+    pub architecture: Option<String>,
+    pub parameter_count: Option<u64>,
+    /// Quantization, e.g. `Q4_K_M`
+    pub file_type: Option<String>,
+    pub context_length: Option<u64>,
+    /// Number of transformer layers
+    pub block_count: Option<u64>,
+    pub embedding_length: Option<u64>,
+    pub head_count: Option<u64>,
+    pub head_count_kv: Option<u64>,
+    /// Per-head key/value widths, when they differ from embedding / heads
+    pub key_length: Option<u64>,
+    pub value_length: Option<u64>,
+    pub chat_template: Option<String>,
+    /// Tokenizer family, e.g. `llama` or `gpt2`
+    pub tokenizer: Option<String>,
+    pub vocab_size: Option<u64>,
+    /// Every key with a printable value, in file order
+    pub metadata: Vec<(String, String)>,
+    pub tensors: Vec<GgufTensor>,
+}
+
+#[derive(Clone)]
+pub struct GgufTensor {
+    /// Transformer block the tensor belongs to, if any
+    pub layer: Option<u32>,
+    pub elements: u64,
+    pub bytes: u64,
+}
+
+/// Memory the machine offers to a model
+#[derive(Clone, Default)]
+pub struct SystemMemory {
+    pub ram_total: u64,
+    pub ram_available: u64,
+    /// Dedicated GPU memory; None when no GPU could be queried
+    pub vram_total: Option<u64>,
+    pub gpu_name: Option<String>,
+    /// GPU shares system RAM (Apple Silicon)
+    pub unified: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FitVerdict {
+    /// Every layer fits in GPU memory
+    Gpu,
+    /// Only some layers can be offloaded
+    Partial,
+    /// Runs from system RAM only
+    Cpu,
+    /// Fits, but needs more RAM than is currently free
+    Tight,
+    TooBig,
+    /// A GPU backend is in use but its memory could not be read
+    VramUnknown,
+}
+
+/// Predicted memory use of a model under a launch profile
+#[derive(Clone)]
+pub struct FitEstimate {
+    pub weights: u64,
+    pub kv_cache: u64,
+    /// Compute buffers and other runtime allocations
+    pub overhead: u64,
+    /// Context size the estimate was made for
+    pub ctx: u64,
+    pub vram_needed: u64,
+    pub ram_needed: u64,
+    /// Layers that fit on the GPU; the block count plus one means all
+    pub suggested_ngl: u32,
+    pub layers: u32,
+    pub verdict: FitVerdict,
+}
+
+#[derive(Deserialize, Clone)]
 pub struct GhAsset {
     pub name: String,
     pub browser_download_url: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Clone)]
 pub struct GhRelease {
+    #[serde(default)]
+    pub tag_name: String,
+    /// Release notes in Markdown
+    #[serde(default)]
+    pub body: String,
+    #[serde(default)]
+    pub html_url: String,
+    #[serde(default)]
+    pub published_at: String,
     pub assets: Vec<GhAsset>,
 }
 
+/// Body of `/v1/chat/completions`. Only the fields a server of the given
+/// kind understands are sent, since strict servers reject unknown ones.
 #[derive(Serialize)]
 pub struct ChatReq {
     pub model: String,
     pub messages: Vec<Msg>,
     pub stream: bool,
+    pub stream_options: StreamOptions,
     pub temperature: f32,
-    pub max_tokens: i32,
+    pub top_p: f32,
+    pub presence_penalty: f32,
+    pub frequency_penalty: f32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    /// Left out for -1, which means no limit
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<i32>,
+    /// Left out for -1, which means a random seed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(flatten)]
+    pub llama: Option<LlamaSampling>,
+}
+
+impl ChatReq {
+    pub fn new(kind: ConnectionKind, model: String, messages: Vec<Msg>, s: SamplingParams) -> Self {
+        Self {
+            model,
+            messages,
+            stream: true,
+            stream_options: StreamOptions { include_usage: true },
+            temperature: s.temperature,
+            top_p: s.top_p,
+            presence_penalty: s.presence_penalty,
+            frequency_penalty: s.frequency_penalty,
+            max_tokens: (s.max_tokens >= 0).then_some(s.max_tokens),
+            seed: (s.seed >= 0).then_some(s.seed),
+            llama: kind.is_llama_server().then_some(LlamaSampling {
+                top_k: s.top_k,
+                min_p: s.min_p,
+                repeat_penalty: s.repeat_penalty,
+                mirostat: s.mirostat,
+                mirostat_tau: s.mirostat_tau,
+                mirostat_eta: s.mirostat_eta,
+            }),
+            stop: s.stop,
+        }
+    }
+}
+
+/// Samplers only llama-server accepts
+#[derive(Serialize)]
+pub struct LlamaSampling {
+    pub top_k: i32,
+    pub min_p: f32,
+    pub repeat_penalty: f32,
+    pub mirostat: u8,
+    pub mirostat_tau: f32,
+    pub mirostat_eta: f32,
+}
+
+#[derive(Serialize)]
+pub struct StreamOptions {
+    /// Ask for a final chunk carrying `usage`
+    pub include_usage: bool,
 }
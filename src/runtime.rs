@@ -1,42 +1,102 @@
 use crate::models::*;
 use crate::download::*;
-use crate::server::{find_server_bin, guess_backend, pick_asset_url, want_asset_name};
-use crate::spawn::*;
+use crate::server::{find_server_bin, pick_asset_url, want_asset_name};
 use std::{
     fs,
-    path::PathBuf,
-    sync::mpsc,
+    path::{Path, PathBuf},
 };
 
-pub fn ensure_runtime(app: &mut crate::app::App) -> anyhow::Result<()> {
-    app.status = "Checking runtime…".into();
-    let be = if app.backend == Backend::Auto {
-        guess_backend()
-    } else {
-        app.backend
-    };
-    let rel: GhRelease = reqwest::blocking::Client::new()
+/// Directory holding one subdirectory per installed `<tag>-<backend>`
+pub const RUNTIMES_DIR: &str = "runtimes";
+/// Single install location used before versioned runtimes
+pub const LEGACY_BIN_DIR: &str = "llama-bin";
+
+pub fn backend_slug(b: Backend) -> &'static str {
+    match b {
+        Backend::Auto => "auto",
+        Backend::Cpu => "cpu",
+        Backend::Cuda => "cuda",
+        Backend::Hip => "hip",
+        Backend::Metal => "metal",
+        Backend::Vulkan => "vulkan",
+    }
+}
+
+/// Split an install directory name like `b1234-vulkan` into tag and backend
+pub fn parse_runtime_dir(name: &str) -> Option<(String, Backend)> {
+    let (tag, slug) = name.rsplit_once('-')?;
+    let backend = [Backend::Cpu, Backend::Cuda, Backend::Hip, Backend::Metal, Backend::Vulkan]
+        .into_iter()
+        .find(|b| backend_slug(*b) == slug)?;
+    Some((tag.to_string(), backend))
+}
+
+/// Build number of a llama.cpp release tag (`b1234`), for ordering
+pub fn tag_number(tag: &str) -> Option<u64> {
+    tag.trim_start_matches('b').parse().ok()
+}
+
+pub fn runtime_install_dir(runtime_dir: &Path, tag: &str, backend: Backend) -> PathBuf {
+    runtime_dir
+        .join(RUNTIMES_DIR)
+        .join(format!("{tag}-{}", backend_slug(backend)))
+}
+
+pub fn fetch_latest_release() -> anyhow::Result<GhRelease> {
+    Ok(reqwest::blocking::Client::new()
         .get("https://api.github.com/repos/ggml-org/llama.cpp/releases/latest")
         .header("User-Agent", "llama-mini")
         .send()?
-        .json()?;
-    let url = pick_asset_url(&rel, want_asset_name(be))
+        .error_for_status()?
+        .json()?)
+}
+
+/// Queue the download of a release build for a backend into its own
+/// directory. Returns false when it is already installed or queued.
+pub fn install_runtime(app: &mut crate::app::App, rel: &GhRelease, be: Backend) -> anyhow::Result<bool> {
+    let url = pick_asset_url(rel, want_asset_name(be))
         .ok_or_else(|| anyhow::anyhow!("No matching asset for backend"))?;
-    let zip_path = app.runtime_dir.join("llama-runtime.zip");
-    let bin_dir = app.runtime_dir.join("llama-bin");
-    if !bin_dir.exists() {
-        let (tx, rx) = mpsc::channel();
-        app.dl_rx = Some(rx);
-        app.runtime_progress = Some((0, None, "download".into()));
-        spawn_runtime_download(url, zip_path, bin_dir, tx);
-        app.status = "Downloading runtime…".into();
-    } else {
-        app.server_bin = find_server_bin(&bin_dir);
-        if app.server_bin.is_none() {
-            anyhow::bail!("llama-server not found")
-        }
-        app.status = "Runtime ready".into();
+    let bin_dir = runtime_install_dir(&app.runtime_dir, &rel.tag_name, be);
+    if find_server_bin(&bin_dir).is_some() || app.downloads.find_active(&bin_dir).is_some() {
+        return Ok(false);
+    }
+    fs::create_dir_all(app.runtime_dir.join(RUNTIMES_DIR))?;
+    let zip_path = bin_dir.with_extension("zip");
+    let label = format!("llama.cpp {} ({})", rel.tag_name, backend_slug(be));
+    app.downloads.enqueue(
+        DownloadKind::Runtime,
+        label,
+        JobSpec::Runtime {
+            url,
+            zip_path,
+            bin_dir,
+        },
+    );
+    Ok(true)
+}
+
+/// Make sure the latest release for the current backend is installed
+/// (or being installed), selecting it when it already is
+pub fn ensure_runtime(app: &mut crate::app::App) -> anyhow::Result<()> {
+    app.status = "Checking runtime…".into();
+    let be = app.effective_backend();
+    let rel = fetch_latest_release()?;
+    if install_runtime(app, &rel, be)? {
+        app.status = format!("Downloading runtime {}…", rel.tag_name);
+        return Ok(());
     }
+    let bin_dir = runtime_install_dir(&app.runtime_dir, &rel.tag_name, be);
+    if app.downloads.find_active(&bin_dir).is_some() {
+        app.status = "Runtime download already queued".into();
+        return Ok(());
+    }
+    if find_server_bin(&bin_dir).is_none() {
+        anyhow::bail!("llama-server not found in {}", bin_dir.display())
+    }
+    app.detect_runtimes();
+    let name = format!("{}-{}", rel.tag_name, backend_slug(be));
+    app.select_runtime(&name);
+    app.status = format!("Runtime {name} is the latest");
     Ok(())
 }
 
@@ -46,18 +106,30 @@ pub fn start_model_download(app: &mut crate::app::App) -> anyhow::Result<()> {
     if repo.is_empty() || file.is_empty() {
         anyhow::bail!("Set model repo and file")
     }
-    let dest = app.model_dir.join(&file);
-    if dest.exists() {
+    // Split models are fetched as a whole set and stored flat in model_dir
+    let files: Vec<(String, PathBuf)> = crate::shards::shard_set(&file)
+        .into_iter()
+        .map(|f| {
+            let dest = app.model_dir.join(crate::shards::file_name_of(&f));
+            (f, dest)
+        })
+        .collect();
+    let dest = files[0].1.clone();
+    if files.iter().all(|(_, d)| d.exists()) {
         app.model_path = Some(dest.clone());
         app.status = "Model already downloaded".into();
         crate::scan::scan_downloaded_models(app);
         return Ok(());
     }
-    let url = format!("https://huggingface.co/{repo}/resolve/main/{file}?download=true");
-    let (tx, rx) = mpsc::channel();
-    app.dl_rx = Some(rx);
-    app.model_progress = Some((0, None, "download".into()));
-    crate::model_download::spawn_model_download(url, dest, tx);
+    if app.downloads.find_active(&dest).is_some() {
+        app.status = format!("{file} is already in the download queue");
+        return Ok(());
+    }
+    let label = match files.len() {
+        1 => format!("{repo}/{file}"),
+        n => format!("{repo}/{file} ({n} parts)"),
+    };
+    app.downloads.enqueue(DownloadKind::Model, label, JobSpec::Model { repo, files });
     app.status = "Downloading model…".into();
     Ok(())
 }
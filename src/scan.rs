@@ -1,29 +1,67 @@
 use crate::models::DownloadedModel;
-use std::fs;
+use crate::shards::parse_shard;
+use crate::gguf::read_model_info;
+use std::{fs, sync::Arc};
 
 pub fn scan_downloaded_models(app: &mut crate::app::App) {
-    let mut list = Vec::new();
+    let mut list: Vec<DownloadedModel> = Vec::new();
     if let Ok(read) = fs::read_dir(&app.model_dir) {
-        for ent in read.flatten() {
-            let p = ent.path();
-            if p.is_file() {
-                if let Some(name) = p
-                    .file_name()
-                    .and_then(|s| s.to_str())
-                    .map(|s| s.to_string())
-                {
-                    if name.to_lowercase().ends_with(".gguf") {
-                        let size = ent.metadata().ok().map(|m| m.len());
-                        list.push(DownloadedModel {
-                            file_name: name,
-                            path: p.clone(),
-                            size,
-                        });
+        let mut files: Vec<_> = read
+            .flatten()
+            .map(|ent| ent.path())
+            .filter(|p| p.is_file())
+            .collect();
+        files.sort();
+        for p in files {
+            if let Some(name) = p
+                .file_name()
+                .and_then(|s| s.to_str())
+                .map(|s| s.to_string())
+            {
+                if !name.to_lowercase().ends_with(".gguf") {
+                    continue;
+                }
+                let size = fs::metadata(&p).ok().map(|m| m.len());
+                // Later shards join the entry of their set
+                if let Some(shard) = parse_shard(&name) {
+                    let set = list.iter_mut().find(|m| {
+                        m.expected_parts == shard.count
+                            && parse_shard(&m.file_name).is_some_and(|s| s.base == shard.base)
+                    });
+                    if let Some(set) = set {
+                        set.parts.push(p.clone());
+                        set.size = set.size.zip(size).map(|(a, b)| a + b);
+                        if shard.index == 1 {
+                            set.file_name = name;
+                            set.path = p;
+                        }
+                        continue;
                     }
                 }
+                list.push(DownloadedModel {
+                    file_name: name.clone(),
+                    path: p.clone(),
+                    size,
+                    parts: vec![p.clone()],
+                    expected_parts: parse_shard(&name).map(|s| s.count).unwrap_or(1),
+                    info: Err(String::new()),
+                });
             }
         }
     }
+    // Headers are only re-read for models that changed since the last scan
+    for m in &mut list {
+        let known = app
+            .downloaded
+            .iter()
+            .find(|old| old.path == m.path && old.size == m.size && old.parts == m.parts);
+        m.info = match known {
+            Some(old) => old.info.clone(),
+            None => read_model_info(&m.parts)
+                .map(Arc::new)
+                .map_err(|e| e.to_string()),
+        };
+    }
     list.sort_by(|a, b| {
         let ma = fs::metadata(&a.path).ok().and_then(|m| m.modified().ok());
         let mb = fs::metadata(&b.path).ok().and_then(|m| m.modified().ok());
@@ -68,15 +68,164 @@ pub fn pick_asset_url(rel: &GhRelease, pats: &[&str]) -> Option<String> {
 
 pub fn find_server_bin(dir: &PathBuf) -> Option<PathBuf> {
     let names = ["llama-server", "server", "llama-server.exe", "server.exe"];
-    for n in names {
-        let p = dir.join(n);
-        if p.exists() {
-            return Some(p);
+    // Some release archives keep the binaries under build/bin
+    for d in [dir.clone(), dir.join("build").join("bin")] {
+        for n in names {
+            let p = d.join(n);
+            if p.exists() {
+                return Some(p);
+            }
         }
     }
     None
 }
 
+/// Split free-form extra arguments on whitespace, keeping quoted runs together
+pub fn split_args(s: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut cur = String::new();
+    let mut quote: Option<char> = None;
+    let mut has_token = false;
+    for c in s.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => cur.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                has_token = true;
+            }
+            None if c.is_whitespace() => {
+                if has_token || !cur.is_empty() {
+                    out.push(std::mem::take(&mut cur));
+                    has_token = false;
+                }
+            }
+            None => cur.push(c),
+        }
+    }
+    if has_token || !cur.is_empty() {
+        out.push(cur);
+    }
+    out
+}
+
+/// llama-server arguments for a launch profile, excluding model, host and port
+pub fn launch_args(profile: &LaunchProfile, backend: Backend) -> Vec<String> {
+    let mut args = Vec::new();
+    let ngl = if profile.gpu_layers >= 0 {
+        profile.gpu_layers
+    } else if backend == Backend::Cpu {
+        0
+    } else {
+        99
+    };
+    args.extend(["-ngl".to_string(), ngl.to_string()]);
+    if profile.ctx_size > 0 {
+        args.extend(["-c".to_string(), profile.ctx_size.to_string()]);
+    }
+    if profile.threads > 0 {
+        args.extend(["-t".to_string(), profile.threads.to_string()]);
+    }
+    if profile.batch_size > 0 {
+        args.extend(["-b".to_string(), profile.batch_size.to_string()]);
+    }
+    if profile.flash_attn {
+        args.extend(["--flash-attn".to_string(), "on".to_string()]);
+    }
+    if profile.mlock {
+        args.push("--mlock".into());
+    }
+    if !profile.mmap {
+        args.push("--no-mmap".into());
+    }
+    if !profile.cache_type_k.is_empty() && profile.cache_type_k != "f16" {
+        args.extend(["--cache-type-k".to_string(), profile.cache_type_k.clone()]);
+    }
+    if !profile.cache_type_v.is_empty() && profile.cache_type_v != "f16" {
+        args.extend(["--cache-type-v".to_string(), profile.cache_type_v.clone()]);
+    }
+    if profile.parallel > 1 {
+        args.extend(["-np".to_string(), profile.parallel.to_string()]);
+    }
+    args.extend(split_args(&profile.extra_args));
+    args
+}
+
+pub const SERVER_HOST: &str = "127.0.0.1";
+const PREFERRED_PORT: u16 = 8080;
+
+fn port_is_free(port: u16) -> bool {
+    std::net::TcpListener::bind((SERVER_HOST, port)).is_ok()
+}
+
+/// Honor a configured port if it is free, otherwise prefer 8080 and fall
+/// back to whatever the OS hands out
+pub fn pick_port(configured: u16) -> anyhow::Result<u16> {
+    if configured != 0 {
+        if port_is_free(configured) {
+            return Ok(configured);
+        }
+        anyhow::bail!("port {configured} is already in use");
+    }
+    if port_is_free(PREFERRED_PORT) {
+        return Ok(PREFERRED_PORT);
+    }
+    let listener = std::net::TcpListener::bind((SERVER_HOST, 0))?;
+    Ok(listener.local_addr()?.port())
+}
+
+pub fn server_url_for(port: u16) -> String {
+    format!("http://{SERVER_HOST}:{port}")
+}
+
+/// Recognize llama-server log lines that explain a fatal failure
+pub fn classify_fatal_line(line: &str) -> Option<ServerFault> {
+    let l = line.to_lowercase();
+    if l.contains("out of memory")
+        || l.contains("outofdevicememory")
+        || l.contains("failed to allocate")
+        || l.contains("cudamalloc failed")
+        || l.contains("unable to allocate")
+    {
+        Some(ServerFault::OutOfMemory)
+    } else if l.contains("unknown model architecture") || l.contains("unsupported model architecture") {
+        Some(ServerFault::UnsupportedArch)
+    } else if l.contains("invalid magic")
+        || l.contains("failed to read magic")
+        || l.contains("not within the file bounds")
+        || l.contains("gguf_init_from_file")
+        || l.contains("failed to load model")
+    {
+        Some(ServerFault::BadModelFile)
+    } else if l.contains("couldn't bind") || l.contains("address already in use") {
+        Some(ServerFault::PortInUse)
+    } else {
+        None
+    }
+}
+
+/// Ask llama-server to shut down cleanly; on platforms without signals
+/// this falls back to killing it
+pub fn request_terminate(child: &mut std::process::Child) {
+    #[cfg(unix)]
+    {
+        let sent = std::process::Command::new("kill")
+            .args(["-TERM", &child.id().to_string()])
+            .status()
+            .map(|s| s.success())
+            .unwrap_or(false);
+        if sent {
+            return;
+        }
+    }
+    let _ = child.kill();
+}
+
+/// Delay before the next automatic restart: 1s, 2s, 4s, ... capped at 30s
+pub fn restart_backoff(attempt: u32) -> std::time::Duration {
+    std::time::Duration::from_secs((1u64 << attempt.min(5)).min(30))
+}
+
 pub fn start_server(app: &mut crate::app::App) -> anyhow::Result<()> {
     let exe = app
         .server_bin
@@ -86,32 +235,30 @@ pub fn start_server(app: &mut crate::app::App) -> anyhow::Result<()> {
         .model_path
         .clone()
         .ok_or_else(|| anyhow::anyhow!("no model"))?;
-    let ngl = match app.backend {
-        crate::models::Backend::Cuda | crate::models::Backend::Hip | crate::models::Backend::Metal | crate::models::Backend::Vulkan | crate::models::Backend::Auto => "99",
-        _ => "0",
-    };
+    let profile = app.launch_profile_for(&mdl);
+    let port = pick_port(app.settings.server_port)?;
+    app.server_port = Some(port);
+    app.server_url = server_url_for(port);
     app.loaded_model = Some(mdl.to_string_lossy().to_string());
     app.served_model_id = None;
     app.server_ready = false;
+    app.server_fault = None;
+    app.server_exited = None;
     app.status = "Server starting…".into();
 
     let mut child = std::process::Command::new(exe)
-        .args([
-            "-m",
-            mdl.to_string_lossy().as_ref(),
-            "-ngl",
-            ngl,
-            "--port",
-            "8080",
-            "--host",
-            "127.0.0.1",
-        ])
+        .args(["-m", mdl.to_string_lossy().as_ref()])
+        .args(launch_args(&profile, app.backend))
+        .args(["--port", &port.to_string(), "--host", SERVER_HOST])
+        // Exposes /metrics for the performance dashboard
+        .arg("--metrics")
         .stdout(std::process::Stdio::piped())
         .stderr(std::process::Stdio::piped())
         .spawn()?;
 
     let (tx, rx) = std::sync::mpsc::channel();
     app.log_rx = Some(rx);
+    let _ = tx.send(format!("[APP] Started llama-server on port {port} with {}", mdl.display()));
     if let Some(stdout) = child.stdout.take() {
         let txo = tx.clone();
         std::thread::spawn(move || {
@@ -141,26 +288,33 @@ pub fn start_server(app: &mut crate::app::App) -> anyhow::Result<()> {
 
     let url = app.server_url.clone();
     let tx_ready = tx.clone();
+    let alive = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(true));
+    app.server_alive = alive.clone();
     std::thread::spawn(move || {
         let client = reqwest::blocking::Client::new();
-        for _ in 0..60 {
+        // Large models can take minutes to load; give up only when the
+        // process is gone or after ten minutes
+        for _ in 0..1200 {
+            if !alive.load(std::sync::atomic::Ordering::Relaxed) {
+                return;
+            }
             match client.get(format!("{}/v1/models", url)).send() {
                 Ok(resp) if resp.status().is_success() => {
-                    let id = resp.json::<serde_json::Value>().ok().and_then(|v| {
-                        v["data"]
-                            .get(0)
-                            .and_then(|x| x["id"].as_str())
-                            .map(|s| s.to_string())
-                    });
+                    let id = resp
+                        .json::<crate::models::ModelList>()
+                        .ok()
+                        .and_then(|l| l.data.into_iter().next())
+                        .map(|m| m.id);
                     let _ = tx_ready.send("[READY] llama-server is ready".into());
                     if let Some(mid) = id {
                         let _ = tx_ready.send(format!("[MODEL] {mid}"));
                     }
-                    break;
+                    return;
                 }
                 _ => std::thread::sleep(std::time::Duration::from_millis(500)),
             }
         }
+        let _ = tx_ready.send("[TIMEOUT] llama-server did not become ready".into());
     });
 
     app.server_child = Some(child);
@@ -0,0 +1,185 @@
+use crate::models::HFFile;
+use std::path::Path;
+
+/// A file that belongs to a split GGUF set such as `name-00001-of-00005.gguf`
+pub struct ShardName {
+    /// File name with the `-NNNNN-of-NNNNN` suffix removed, e.g. `name.gguf`
+    pub base: String,
+    pub index: u32,
+    pub count: u32,
+}
+
+pub fn parse_shard(file_name: &str) -> Option<ShardName> {
+    let lower = file_name.to_lowercase();
+    let stem = file_name.get(..lower.strip_suffix(".gguf")?.len())?;
+    let (rest, count) = stem.rsplit_once("-of-")?;
+    let (prefix, index) = rest.rsplit_once('-')?;
+    if count.len() != 5 || index.len() != 5 {
+        return None;
+    }
+    let count: u32 = count.parse().ok()?;
+    let index: u32 = index.parse().ok()?;
+    if count < 2 || index == 0 || index > count {
+        return None;
+    }
+    Some(ShardName {
+        base: format!("{prefix}.gguf"),
+        index,
+        count,
+    })
+}
+
+/// Every file name of the split set `path` belongs to, in order. Works on
+/// repo paths too (`Q4_K_M/name-00002-of-00003.gguf`) and returns a single
+/// entry for ordinary files.
+pub fn shard_set(path: &str) -> Vec<String> {
+    let (dir, name) = match path.rsplit_once('/') {
+        Some((d, n)) => (format!("{d}/"), n),
+        None => (String::new(), path),
+    };
+    let Some(shard) = parse_shard(name) else {
+        return vec![path.to_string()];
+    };
+    let prefix = shard.base.trim_end_matches(".gguf");
+    (1..=shard.count)
+        .map(|i| format!("{dir}{prefix}-{i:05}-of-{:05}.gguf", shard.count))
+        .collect()
+}
+
+pub fn is_first_or_single(file_name: &str) -> bool {
+    parse_shard(file_name).is_none_or(|s| s.index == 1)
+}
+
+/// One downloadable model in a repo: a single GGUF or a full shard set
+#[derive(Clone)]
+pub struct HFFileGroup {
+    /// Display name; the set's base name for split models
+    pub name: String,
+    /// Files in shard order; the first one is what llama-server loads
+    pub files: Vec<HFFile>,
+}
+
+impl HFFileGroup {
+    pub fn size(&self) -> Option<u64> {
+        self.files.iter().map(|f| f.expected_size()).sum()
+    }
+}
+
+pub fn group_hf_files(files: &[HFFile]) -> Vec<HFFileGroup> {
+    let mut groups: Vec<HFFileGroup> = Vec::new();
+    for f in files {
+        let name = match f.rfilename.rsplit_once('/') {
+            Some((dir, n)) => parse_shard(n).map(|s| format!("{dir}/{}", s.base)),
+            None => parse_shard(&f.rfilename).map(|s| s.base),
+        };
+        match name {
+            Some(name) => match groups.iter_mut().find(|g| g.name == name) {
+                Some(g) => g.files.push(f.clone()),
+                None => groups.push(HFFileGroup {
+                    name,
+                    files: vec![f.clone()],
+                }),
+            },
+            None => groups.push(HFFileGroup {
+                name: f.rfilename.clone(),
+                files: vec![f.clone()],
+            }),
+        }
+    }
+    for g in &mut groups {
+        g.files.sort_by(|a, b| a.rfilename.cmp(&b.rfilename));
+    }
+    groups
+}
+
+pub fn file_name_of(path: &str) -> &str {
+    Path::new(path)
+        .file_name()
+        .and_then(|s| s.to_str())
+        .unwrap_or(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(name: &str, size: u64) -> HFFile {
+        HFFile {
+            rfilename: name.into(),
+            size: Some(size),
+            lfs: None,
+        }
+    }
+
+    #[test]
+    fn parses_shard_names() {
+        let s = parse_shard("Model-Q4_K_M-00002-of-00003.gguf").unwrap();
+        assert_eq!((s.base.as_str(), s.index, s.count), ("Model-Q4_K_M.gguf", 2, 3));
+        let s = parse_shard("model-00001-of-00002.GGUF").unwrap();
+        assert_eq!((s.base.as_str(), s.index, s.count), ("model.gguf", 1, 2));
+    }
+
+    #[test]
+    fn rejects_non_shard_names() {
+        for name in [
+            "model.gguf",
+            "model-Q4_K_M.gguf",
+            "model-00001-of-00003.bin",
+            "model-1-of-3.gguf",
+            "model-00001-of-00001.gguf",
+            "model-00000-of-00003.gguf",
+            "model-00004-of-00003.gguf",
+            "model-0000a-of-00003.gguf",
+            "00001-of-00003.gguf",
+        ] {
+            assert!(parse_shard(name).is_none(), "{name}");
+        }
+    }
+
+    #[test]
+    fn shard_set_lists_every_part() {
+        assert_eq!(
+            shard_set("m-00002-of-00003.gguf"),
+            ["m-00001-of-00003.gguf", "m-00002-of-00003.gguf", "m-00003-of-00003.gguf"]
+        );
+        assert_eq!(
+            shard_set("Q8_0/m-00001-of-00002.gguf"),
+            ["Q8_0/m-00001-of-00002.gguf", "Q8_0/m-00002-of-00002.gguf"]
+        );
+        assert_eq!(shard_set("dir/model.gguf"), ["dir/model.gguf"]);
+    }
+
+    #[test]
+    fn first_or_single() {
+        assert!(is_first_or_single("model.gguf"));
+        assert!(is_first_or_single("m-00001-of-00002.gguf"));
+        assert!(!is_first_or_single("m-00002-of-00002.gguf"));
+    }
+
+    #[test]
+    fn groups_shards_by_directory() {
+        let files = [
+            file("Q8_0/m-00002-of-00002.gguf", 20),
+            file("m-Q4_K_M.gguf", 5),
+            file("Q8_0/m-00001-of-00002.gguf", 10),
+            file("F16/m-00001-of-00002.gguf", 30),
+            file("F16/m-00002-of-00002.gguf", 40),
+        ];
+        let groups = group_hf_files(&files);
+        let names: Vec<&str> = groups.iter().map(|g| g.name.as_str()).collect();
+        assert_eq!(names, ["Q8_0/m.gguf", "m-Q4_K_M.gguf", "F16/m.gguf"]);
+
+        let q8 = &groups[0];
+        let parts: Vec<&str> = q8.files.iter().map(|f| f.rfilename.as_str()).collect();
+        assert_eq!(parts, ["Q8_0/m-00001-of-00002.gguf", "Q8_0/m-00002-of-00002.gguf"]);
+        assert_eq!(q8.size(), Some(30));
+        assert_eq!(groups[1].files.len(), 1);
+        assert_eq!(groups[2].size(), Some(70));
+    }
+
+    #[test]
+    fn file_name_strips_directories() {
+        assert_eq!(file_name_of("Q8_0/m.gguf"), "m.gguf");
+        assert_eq!(file_name_of("m.gguf"), "m.gguf");
+    }
+}
@@ -1,4 +1,4 @@
-use crate::models::{DownloadEvent, DownloadKind};
+use crate::models::{DownloadEvent, DownloadKind, JobControl, JobId};
 use crate::unzip::*;
 use std::{
     fs,
@@ -8,29 +8,60 @@ use std::{
 };
 
 pub fn spawn_runtime_download(
+    job: JobId,
     url: String,
     zip_path: PathBuf,
     bin_dir: PathBuf,
+    control: JobControl,
     tx: mpsc::Sender<DownloadEvent>,
 ) {
     std::thread::spawn(move || {
         let client = reqwest::blocking::Client::new();
-        let resp = match client.get(&url).send() {
+        // A paused download left its zip behind; ask for the rest
+        let mut offset = fs::metadata(&zip_path).map(|m| m.len()).unwrap_or(0);
+        let mut req = client.get(&url);
+        if offset > 0 {
+            req = req.header(reqwest::header::RANGE, format!("bytes={offset}-"));
+        }
+        let resp = match req.send() {
             Ok(r) => r,
             Err(e) => {
                 let _ = tx.send(DownloadEvent::Error {
+                    job,
                     kind: DownloadKind::Runtime,
                     err: e.to_string(),
                 });
                 return;
             }
         };
-        let total = resp.content_length();
+        let status = resp.status();
+        let complete = status == reqwest::StatusCode::RANGE_NOT_SATISFIABLE;
+        if !status.is_success() && !complete {
+            let _ = tx.send(DownloadEvent::Error {
+                job,
+                kind: DownloadKind::Runtime,
+                err: format!("HTTP {status}"),
+            });
+            return;
+        }
+        let resumed = status == reqwest::StatusCode::PARTIAL_CONTENT;
+        if !resumed && !complete {
+            // Server ignored the range request; start over
+            offset = 0;
+        }
+        let total = resp.content_length().map(|l| l + offset);
         let mut reader = resp;
-        let mut file = match fs::File::create(&zip_path) {
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resumed || complete)
+            .truncate(!resumed && !complete)
+            .open(&zip_path);
+        let mut file = match file {
             Ok(f) => f,
             Err(e) => {
                 let _ = tx.send(DownloadEvent::Error {
+                    job,
                     kind: DownloadKind::Runtime,
                     err: e.to_string(),
                 });
@@ -38,45 +69,69 @@ pub fn spawn_runtime_download(
             }
         };
         let mut buf = [0u8; 64 * 1024];
-        let mut downloaded: u64 = 0;
-        loop {
-            match reader.read(&mut buf) {
-                Ok(0) => break,
-                Ok(n) => {
-                    if let Err(e) = file.write_all(&buf[..n]) {
+        let mut downloaded = offset;
+        if !complete {
+            loop {
+                // The zip is kept so a resumed job continues where this stopped;
+                // cancelling removes it once this reports back
+                if control.should_stop() {
+                    let _ = file.sync_all();
+                    let _ = tx.send(DownloadEvent::Stopped { job });
+                    return;
+                }
+                match reader.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => {
+                        if let Err(e) = file.write_all(&buf[..n]) {
+                            let _ = tx.send(DownloadEvent::Error {
+                                job,
+                                kind: DownloadKind::Runtime,
+                                err: e.to_string(),
+                            });
+                            return;
+                        }
+                        downloaded += n as u64;
+                        let _ = tx.send(DownloadEvent::Progress {
+                            job,
+                            kind: DownloadKind::Runtime,
+                            current: downloaded,
+                            total,
+                            stage: "download",
+                        });
+                    }
+                    Err(e) => {
                         let _ = tx.send(DownloadEvent::Error {
+                            job,
                             kind: DownloadKind::Runtime,
                             err: e.to_string(),
                         });
                         return;
                     }
-                    downloaded += n as u64;
-                    let _ = tx.send(DownloadEvent::Progress {
-                        kind: DownloadKind::Runtime,
-                        current: downloaded,
-                        total,
-                        stage: "download",
-                    });
-                }
-                Err(e) => {
-                    let _ = tx.send(DownloadEvent::Error {
-                        kind: DownloadKind::Runtime,
-                        err: e.to_string(),
-                    });
-                    return;
                 }
             }
         }
+        drop(file);
         // Unpack
         if !bin_dir.exists() {
             let _ = fs::create_dir_all(&bin_dir);
         }
-        if let Err(e) = unzip_with_progress(&zip_path, &bin_dir, &tx) {
+        if let Err(e) = unzip_with_progress(&zip_path, &bin_dir, job, &tx) {
+            // A half-extracted folder would look installed on the next start,
+            // and a broken archive must not be resumed on retry
+            let _ = fs::remove_dir_all(&bin_dir);
+            let _ = fs::remove_file(&zip_path);
             let _ = tx.send(DownloadEvent::Error {
+                job,
                 kind: DownloadKind::Runtime,
                 err: e.to_string(),
             });
             return;
         }
+        let _ = fs::remove_file(&zip_path);
+        let _ = tx.send(DownloadEvent::Done {
+            job,
+            kind: DownloadKind::Runtime,
+            dest: Some(bin_dir),
+        });
     });
 }
@@ -0,0 +1,159 @@
+/// One dispatched server-sent event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SseEvent {
+    /// Empty unless the stream named it with an `event:` field
+    pub event: String,
+    pub data: String,
+}
+
+/// Incremental `text/event-stream` decoder following the WHATWG rules:
+/// lines may end in CRLF, LF or CR, `data:` fields accumulate, lines
+/// starting with `:` are comments and a blank line dispatches the event.
+/// Bytes are only decoded once a whole line is buffered, so multibyte
+/// characters split across reads survive.
+#[derive(Default)]
+pub struct SseDecoder {
+    line: Vec<u8>,
+    data: String,
+    has_data: bool,
+    event: String,
+    /// The last chunk ended in CR; a LF opening the next one belongs to it
+    after_cr: bool,
+    started: bool,
+}
+
+impl SseDecoder {
+    pub fn feed(&mut self, bytes: &[u8]) -> Vec<SseEvent> {
+        let mut out = Vec::new();
+        let mut bytes = bytes;
+        if !self.started && !bytes.is_empty() {
+            self.started = true;
+            // Skip a leading UTF-8 BOM, as long as it arrives in one piece
+            bytes = bytes.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(bytes);
+        }
+        for &b in bytes {
+            let after_cr = std::mem::take(&mut self.after_cr);
+            match b {
+                b'\n' if after_cr => {}
+                b'\r' | b'\n' => {
+                    self.after_cr = b == b'\r';
+                    let line = std::mem::take(&mut self.line);
+                    if let Some(ev) = self.process_line(&String::from_utf8_lossy(&line)) {
+                        out.push(ev);
+                    }
+                }
+                _ => self.line.push(b),
+            }
+        }
+        out
+    }
+
+    fn process_line(&mut self, line: &str) -> Option<SseEvent> {
+        if line.is_empty() {
+            let event = std::mem::take(&mut self.event);
+            if !std::mem::take(&mut self.has_data) {
+                return None;
+            }
+            let mut data = std::mem::take(&mut self.data);
+            data.pop(); // trailing newline added after the last data line
+            return Some(SseEvent { event, data });
+        }
+        if line.starts_with(':') {
+            return None;
+        }
+        let (field, value) = match line.split_once(':') {
+            Some((f, v)) => (f, v.strip_prefix(' ').unwrap_or(v)),
+            None => (line, ""),
+        };
+        match field {
+            "data" => {
+                self.data.push_str(value);
+                self.data.push('\n');
+                self.has_data = true;
+            }
+            "event" => self.event = value.to_string(),
+            // `id` and `retry` only matter for reconnecting, which a chat
+            // completion never does
+            _ => {}
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(events: &[SseEvent]) -> Vec<&str> {
+        events.iter().map(|e| e.data.as_str()).collect()
+    }
+
+    #[test]
+    fn splits_on_blank_lines() {
+        let mut d = SseDecoder::default();
+        let events = d.feed(b"data: one\n\ndata: two\n\n");
+        assert_eq!(data(&events), ["one", "two"]);
+    }
+
+    #[test]
+    fn accepts_crlf_and_cr_line_endings() {
+        let mut d = SseDecoder::default();
+        let events = d.feed(b"data: a\r\n\r\ndata: b\r\rdata: c\n\n");
+        assert_eq!(data(&events), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn crlf_split_across_reads_is_one_line_ending() {
+        let mut d = SseDecoder::default();
+        assert!(d.feed(b"data: a\r").is_empty());
+        assert!(d.feed(b"\n").is_empty());
+        assert_eq!(data(&d.feed(b"\r\n")), ["a"]);
+    }
+
+    #[test]
+    fn joins_multi_line_data() {
+        let mut d = SseDecoder::default();
+        let events = d.feed(b"data: first\ndata:second\ndata\n\n");
+        assert_eq!(data(&events), ["first\nsecond\n"]);
+    }
+
+    #[test]
+    fn ignores_comments_and_unknown_fields() {
+        let mut d = SseDecoder::default();
+        let events = d.feed(b": keep-alive\n\nid: 7\nretry: 100\ndata: x\n: note\n\n");
+        assert_eq!(data(&events), ["x"]);
+    }
+
+    #[test]
+    fn keeps_event_name() {
+        let mut d = SseDecoder::default();
+        let events = d.feed(b"event: error\ndata: {}\n\ndata: y\n\n");
+        assert_eq!(events[0].event, "error");
+        assert_eq!(events[1].event, "");
+    }
+
+    #[test]
+    fn multibyte_characters_split_across_reads() {
+        let text = "data: héllo 🌍\n\n".as_bytes();
+        let mut d = SseDecoder::default();
+        let mut events = Vec::new();
+        for b in text {
+            events.extend(d.feed(std::slice::from_ref(b)));
+        }
+        assert_eq!(data(&events), ["héllo 🌍"]);
+    }
+
+    #[test]
+    fn waits_for_the_blank_line() {
+        let mut d = SseDecoder::default();
+        assert!(d.feed(b"data: partial").is_empty());
+        assert!(d.feed(b"\n").is_empty());
+        assert_eq!(data(&d.feed(b"\n")), ["partial"]);
+    }
+
+    #[test]
+    fn strips_bom() {
+        let mut d = SseDecoder::default();
+        assert_eq!(data(&d.feed(b"\xEF\xBB\xBFdata: z\n\n")), ["z"]);
+    }
+}
@@ -1,70 +1,157 @@
-use crate::models::{StreamEvent, ChatReq, Msg};
-use std::{
-    io::Read,
-    sync::mpsc,
-};
+use crate::models::{StreamEvent, ChatReq, FinishReason, Msg, Timings, Usage};
+use crate::sse::SseDecoder;
+use futures_util::future::{abortable, AbortHandle};
+use std::sync::mpsc;
 
-pub fn stream_chat(url: &str, model: String, msgs: Vec<Msg>, tx: mpsc::Sender<StreamEvent>) {
-    let body = serde_json::to_string(&ChatReq {
-        model,
-        messages: msgs,
-        stream: true,
-        temperature: 0.7,
-        max_tokens: 1024,
-    })
-    .unwrap();
-    let url = url.to_string();
+/// Stops a running `stream_chat`. Aborting drops the request at once, even
+/// while it waits for the server, which closes the connection so the server
+/// frees the slot.
+#[derive(Clone)]
+pub struct CancelHandle(AbortHandle);
+
+impl CancelHandle {
+    pub fn cancel(&self) {
+        self.0.abort();
+    }
+}
+
+pub fn stream_chat(
+    url: &str,
+    api_key: Option<String>,
+    mut req: ChatReq,
+    system_prompt: Option<String>,
+    tx: mpsc::Sender<StreamEvent>,
+) -> CancelHandle {
+    let msgs = &mut req.messages;
+    if let Some(prompt) = system_prompt.filter(|p| !p.trim().is_empty()) {
+        msgs.insert(
+            0,
+            Msg {
+                role: "system".into(),
+                content: prompt,
+                ..Default::default()
+            },
+        );
+    }
+    // A trailing empty reply is the placeholder being streamed into; a
+    // non-empty one is a reply the model is asked to continue
+    if msgs.last().is_some_and(|m| m.role == "assistant" && m.content.is_empty()) {
+        msgs.pop();
+    }
+    for m in msgs.iter_mut() {
+        m.model = None;
+        m.finish = None;
+        m.usage = None;
+        m.timings = None;
+    }
+    // Stop sequences are edited one per line; allow a literal "\n" to mean newline
+    req.stop = req
+        .stop
+        .iter()
+        .filter(|s| !s.is_empty())
+        .map(|s| s.replace("\\n", "\n"))
+        .collect();
+    let body = serde_json::to_string(&req).unwrap();
+    let (task, abort) = abortable(run_stream(url.to_string(), api_key, body, tx.clone()));
     std::thread::spawn(move || {
-        let client = reqwest::blocking::Client::new();
-        let post = client
-            .post(format!("{url}/v1/chat/completions"))
-            .header("Content-Type", "application/json")
-            .header("Accept", "text/event-stream")
-            .body(body)
-            .send();
-        let mut resp = match post {
-            Ok(r) => r,
+        let rt = match tokio::runtime::Builder::new_current_thread().enable_all().build() {
+            Ok(rt) => rt,
             Err(e) => {
                 let _ = tx.send(StreamEvent::Error(format!("request failed: {e}")));
                 let _ = tx.send(StreamEvent::Done);
                 return;
             }
         };
-        let mut buf = String::new();
-        let mut chunk = [0u8; 8192];
-        loop {
-            match resp.read(&mut chunk) {
-                Ok(0) => {
-                    let _ = tx.send(StreamEvent::Done);
-                    break;
-                }
-                Ok(n) => {
-                    buf.push_str(&String::from_utf8_lossy(&chunk[..n]));
-                    while let Some(idx) = buf.find("\n\n") {
-                        let mut line = buf[..idx].trim().to_string();
-                        buf = buf[(idx + 2)..].to_string();
-                        if let Some(rest) = line.strip_prefix("data:") {
-                            line = rest.trim().into();
-                        }
-                        if line == "[DONE]" {
-                            let _ = tx.send(StreamEvent::Done);
-                            return;
-                        }
-                        if let Ok(v) = serde_json::from_str::<serde_json::Value>(&line) {
-                            if let Some(t) = v["choices"][0]["delta"]["content"].as_str() {
-                                if tx.send(StreamEvent::Token(t.into())).is_err() {
-                                    return;
-                                }
-                            }
-                        }
+        // An aborted task has already been wound up by whoever cancelled it
+        let _ = rt.block_on(task);
+    });
+    CancelHandle(abort)
+}
+
+async fn run_stream(url: String, api_key: Option<String>, body: String, tx: mpsc::Sender<StreamEvent>) {
+    let client = reqwest::Client::new();
+    let mut req = client
+        .post(format!("{url}/v1/chat/completions"))
+        .header("Content-Type", "application/json")
+        .header("Accept", "text/event-stream")
+        .body(body);
+    if let Some(key) = api_key {
+        req = req.bearer_auth(key);
+    }
+    let mut resp = match req.send().await {
+        Ok(r) if !r.status().is_success() => {
+            let status = r.status();
+            let detail = r.text().await.unwrap_or_default();
+            let _ = tx.send(StreamEvent::Error(format!("HTTP {status}: {}", detail.trim())));
+            let _ = tx.send(StreamEvent::Done);
+            return;
+        }
+        Ok(r) => r,
+        Err(e) => {
+            let _ = tx.send(StreamEvent::Error(format!("request failed: {e}")));
+            let _ = tx.send(StreamEvent::Done);
+            return;
+        }
+    };
+    let mut decoder = SseDecoder::default();
+    loop {
+        match resp.chunk().await {
+            Ok(None) => {
+                let _ = tx.send(StreamEvent::Done);
+                break;
+            }
+            Ok(Some(chunk)) => {
+                for event in decoder.feed(&chunk) {
+                    if !forward_event(&event.data, &tx) {
+                        return;
                     }
                 }
-                Err(e) => {
-                    let _ = tx.send(StreamEvent::Error(format!("read failed: {e}")));
-                    let _ = tx.send(StreamEvent::Done);
-                    break;
-                }
+            }
+            Err(e) => {
+                let _ = tx.send(StreamEvent::Error(format!("read failed: {e}")));
+                let _ = tx.send(StreamEvent::Done);
+                break;
             }
         }
-    });
+    }
+}
+
+/// Turn one completion chunk into stream events; false once the stream
+/// is over or nobody is listening
+fn forward_event(data: &str, tx: &mpsc::Sender<StreamEvent>) -> bool {
+    if data == "[DONE]" {
+        let _ = tx.send(StreamEvent::Done);
+        return false;
+    }
+    let Ok(v) = serde_json::from_str::<serde_json::Value>(data) else {
+        return true;
+    };
+    if let Some(e) = v["error"]["message"].as_str().or(v["error"].as_str()) {
+        let _ = tx.send(StreamEvent::Error(e.to_string()));
+    }
+    let choice = &v["choices"][0];
+    if let Some(t) = choice["delta"]["content"].as_str()
+        && !t.is_empty()
+        && tx.send(StreamEvent::Token(t.into())).is_err()
+    {
+        return false;
+    }
+    if let Some(reason) = choice["finish_reason"].as_str() {
+        let reason = match reason {
+            "length" => FinishReason::Length,
+            _ => FinishReason::Stop,
+        };
+        let _ = tx.send(StreamEvent::Finish(reason));
+    }
+    if !v["usage"].is_null()
+        && let Ok(usage) = serde_json::from_value::<Usage>(v["usage"].clone())
+    {
+        let _ = tx.send(StreamEvent::Usage(usage));
+    }
+    if !v["timings"].is_null()
+        && let Ok(timings) = serde_json::from_value::<Timings>(v["timings"].clone())
+    {
+        let _ = tx.send(StreamEvent::Timings(timings));
+    }
+    true
 }
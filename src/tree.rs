@@ -0,0 +1,250 @@
+use crate::models::Msg;
+use serde::{Deserialize, Serialize};
+
+/// `active` value of a node whose replies were cut off by a restart
+const NO_BRANCH: usize = usize::MAX;
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Node {
+    pub msg: Msg,
+    pub parent: Option<usize>,
+    pub children: Vec<usize>,
+    /// Index into `children` of the branch being shown
+    pub active: usize,
+}
+
+/// Every message of a conversation, including replaced edits and earlier
+/// regenerations. Siblings are alternatives for the same turn.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ChatTree {
+    pub nodes: Vec<Node>,
+    /// Alternative first messages
+    pub roots: Vec<usize>,
+    pub active_root: usize,
+}
+
+impl ChatTree {
+    /// A single branch, for conversations saved before branching existed
+    pub fn from_linear(msgs: &[Msg]) -> Self {
+        let mut tree = Self::default();
+        let mut parent = None;
+        for m in msgs {
+            parent = Some(tree.push(parent, m.clone()));
+        }
+        tree
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    fn children(&self, parent: Option<usize>) -> &[usize] {
+        match parent {
+            Some(p) => &self.nodes[p].children,
+            None => &self.roots,
+        }
+    }
+
+    fn set_active(&mut self, parent: Option<usize>, idx: usize) {
+        match parent {
+            Some(p) => self.nodes[p].active = idx,
+            None => self.active_root = idx,
+        }
+    }
+
+    /// Add a message after `parent` and make it the shown branch
+    pub fn push(&mut self, parent: Option<usize>, msg: Msg) -> usize {
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            msg,
+            parent,
+            children: vec![],
+            active: 0,
+        });
+        match parent {
+            Some(p) => self.nodes[p].children.push(id),
+            None => self.roots.push(id),
+        }
+        let idx = self.children(parent).len() - 1;
+        self.set_active(parent, idx);
+        id
+    }
+
+    /// Write `msgs` into the tree along `path`, extending it as needed. A
+    /// message that differs from a node which already has replies becomes a
+    /// new sibling, so the earlier branch survives. Nodes past the end of
+    /// `msgs` stay in the tree but are no longer shown.
+    pub fn sync(&mut self, path: &mut Vec<usize>, msgs: &[Msg]) {
+        if path.len() > msgs.len() {
+            path.truncate(msgs.len());
+            self.set_active(path.last().copied(), NO_BRANCH);
+        }
+        for (i, m) in msgs.iter().enumerate() {
+            if let Some(&id) = path.get(i) {
+                let node = &mut self.nodes[id];
+                if node.children.is_empty() || (node.msg.role == m.role && node.msg.content == m.content) {
+                    node.msg = m.clone();
+                    continue;
+                }
+                path.truncate(i);
+            }
+            let parent = i.checked_sub(1).map(|p| path[p]);
+            path.push(self.push(parent, m.clone()));
+        }
+    }
+
+    /// Node ids from the first message down the shown branches
+    pub fn active_path(&self) -> Vec<usize> {
+        let mut path = Vec::new();
+        let mut next = self.roots.get(self.active_root).copied();
+        while let Some(id) = next {
+            path.push(id);
+            let node = &self.nodes[id];
+            next = node.children.get(node.active).copied();
+        }
+        path
+    }
+
+    pub fn messages(&self, path: &[usize]) -> Vec<Msg> {
+        path.iter().map(|&id| self.nodes[id].msg.clone()).collect()
+    }
+
+    pub fn parent(&self, id: usize) -> Option<usize> {
+        self.nodes[id].parent
+    }
+
+    /// Position of a node among its siblings, and how many there are
+    pub fn siblings(&self, id: usize) -> (usize, usize) {
+        let list = self.children(self.nodes[id].parent);
+        (list.iter().position(|&c| c == id).unwrap_or(0), list.len())
+    }
+
+    /// Show the sibling `delta` places away from `id`
+    pub fn select_sibling(&mut self, id: usize, delta: isize) {
+        let parent = self.nodes[id].parent;
+        let (pos, count) = self.siblings(id);
+        let idx = (pos as isize + delta).clamp(0, count as isize - 1) as usize;
+        self.set_active(parent, idx);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn msg(role: &str, content: &str) -> Msg {
+        Msg {
+            role: role.into(),
+            content: content.into(),
+            ..Default::default()
+        }
+    }
+
+    fn contents(tree: &ChatTree) -> Vec<String> {
+        tree.messages(&tree.active_path()).into_iter().map(|m| m.content).collect()
+    }
+
+    #[test]
+    fn from_linear_is_one_branch() {
+        let tree = ChatTree::from_linear(&[msg("user", "a"), msg("assistant", "b"), msg("user", "c")]);
+        assert_eq!(contents(&tree), ["a", "b", "c"]);
+        assert_eq!(tree.roots.len(), 1);
+        assert!(tree.active_path().iter().all(|&id| tree.siblings(id) == (0, 1)));
+        assert!(ChatTree::from_linear(&[]).active_path().is_empty());
+    }
+
+    #[test]
+    fn push_makes_the_new_sibling_active() {
+        let mut tree = ChatTree::from_linear(&[msg("user", "q"), msg("assistant", "first")]);
+        let root = tree.active_path()[0];
+        let second = tree.push(Some(root), msg("assistant", "second"));
+        assert_eq!(contents(&tree), ["q", "second"]);
+        assert_eq!(tree.siblings(second), (1, 2));
+        assert_eq!(tree.parent(second), Some(root));
+    }
+
+    #[test]
+    fn select_sibling_switches_and_clamps() {
+        let mut tree = ChatTree::from_linear(&[msg("user", "q"), msg("assistant", "first")]);
+        let root = tree.active_path()[0];
+        tree.push(Some(root), msg("assistant", "second"));
+        let other = tree.push(Some(root), msg("assistant", "third"));
+        tree.push(Some(other), msg("user", "more"));
+
+        tree.select_sibling(other, -1);
+        assert_eq!(contents(&tree), ["q", "second"]);
+        tree.select_sibling(tree.active_path()[1], -5);
+        assert_eq!(contents(&tree), ["q", "first"]);
+        tree.select_sibling(tree.active_path()[1], 5);
+        assert_eq!(contents(&tree), ["q", "third", "more"]);
+    }
+
+    #[test]
+    fn sync_updates_a_leaf_in_place() {
+        let mut tree = ChatTree::default();
+        let mut path = Vec::new();
+        let mut msgs = vec![msg("user", "q"), msg("assistant", "")];
+        tree.sync(&mut path, &msgs);
+        msgs[1].content.push_str("streamed");
+        tree.sync(&mut path, &msgs);
+        assert_eq!(tree.nodes.len(), 2);
+        assert_eq!(contents(&tree), ["q", "streamed"]);
+    }
+
+    #[test]
+    fn sync_branches_when_a_message_with_replies_changes() {
+        let mut tree = ChatTree::default();
+        let mut path = Vec::new();
+        let mut msgs = vec![msg("user", "q"), msg("assistant", "a")];
+        tree.sync(&mut path, &msgs);
+        let old = path[0];
+
+        // Restart from the user message, then send more text into it
+        msgs.truncate(1);
+        msgs[0].content.push_str(" more");
+        msgs.push(msg("assistant", "b"));
+        tree.sync(&mut path, &msgs);
+
+        assert_ne!(path[0], old);
+        assert_eq!(tree.nodes[old].msg.content, "q");
+        assert_eq!(tree.siblings(path[0]), (1, 2));
+        assert_eq!(contents(&tree), ["q more", "b"]);
+        tree.select_sibling(path[0], -1);
+        assert_eq!(contents(&tree), ["q", "a"]);
+    }
+
+    #[test]
+    fn sync_after_truncate_adds_a_sibling_reply() {
+        let mut tree = ChatTree::default();
+        let mut path = Vec::new();
+        let mut msgs = vec![msg("user", "q"), msg("assistant", "a")];
+        tree.sync(&mut path, &msgs);
+        msgs[1] = msg("assistant", "regenerated");
+        path.truncate(1);
+        tree.sync(&mut path, &msgs);
+        assert_eq!(tree.siblings(path[1]), (1, 2));
+        assert_eq!(tree.nodes.len(), 3);
+    }
+
+    #[test]
+    fn sync_truncate_hides_the_cut_replies() {
+        let mut tree = ChatTree::default();
+        let mut path = Vec::new();
+        let mut msgs = vec![msg("user", "q"), msg("assistant", "a"), msg("user", "r")];
+        tree.sync(&mut path, &msgs);
+
+        // Restart from the first message, then reopen without sending
+        msgs.truncate(1);
+        tree.sync(&mut path, &msgs);
+        assert_eq!(path, tree.active_path());
+        assert_eq!(contents(&tree), ["q"]);
+        assert_eq!(tree.nodes.len(), 3);
+
+        // The next reply is shown as a new branch beside the old one
+        msgs.push(msg("assistant", "b"));
+        tree.sync(&mut path, &msgs);
+        assert_eq!(contents(&tree), ["q", "b"]);
+        tree.select_sibling(path[1], -1);
+        assert_eq!(contents(&tree), ["q", "a", "r"]);
+    }
+}
@@ -1,3 +1,5 @@
+use crate::download::human_size;
+use crate::models::{ConnectionHealth, FinishReason, FitEstimate, FitVerdict, Msg};
 use eframe::egui::{self, Color32, Stroke, RichText};
 
 /// Create a button with proper contrast for light backgrounds
@@ -6,6 +8,94 @@ pub fn light_button(text: &str, bg_color: Color32) -> egui::Button {
         .fill(bg_color)
 }
 
+/// Colored one-line verdict of a memory estimate, with the breakdown on hover
+pub fn fit_label(ui: &mut egui::Ui, est: &FitEstimate) {
+    let (color, text) = match est.verdict {
+        FitVerdict::Gpu => (Color32::from_rgb(166, 227, 161), "✅ fits on GPU".to_string()),
+        FitVerdict::Partial => (
+            Color32::from_rgb(249, 226, 175),
+            format!("◐ partial offload, -ngl {}", est.suggested_ngl),
+        ),
+        FitVerdict::Cpu => (Color32::from_rgb(137, 180, 250), "🖥️ fits in RAM".to_string()),
+        FitVerdict::Tight => (Color32::from_rgb(250, 179, 135), "⚠ needs more free RAM".to_string()),
+        FitVerdict::TooBig => (Color32::from_rgb(243, 139, 168), "⛔ too big".to_string()),
+        FitVerdict::VramUnknown => (Color32::from_rgb(186, 194, 222), "❔ GPU memory unknown".to_string()),
+    };
+    let ngl = if est.verdict == FitVerdict::VramUnknown {
+        "unknown (GPU memory could not be read)".to_string()
+    } else if est.suggested_ngl > est.layers {
+        "99 (all layers)".to_string()
+    } else {
+        format!("{} of {} layers", est.suggested_ngl, est.layers)
+    };
+    ui.colored_label(color, text).on_hover_text(format!(
+        "Weights: {}\nKV cache ({} ctx): {}\nBuffers: {}\nVRAM needed: {}\nRAM needed: {}\nSuggested -ngl: {}",
+        human_size(est.weights),
+        est.ctx,
+        human_size(est.kv_cache),
+        human_size(est.overhead),
+        human_size(est.vram_needed),
+        human_size(est.ram_needed),
+        ngl,
+    ));
+}
+
+/// Why a reply ended, shown under it
+pub fn finish_label(ui: &mut egui::Ui, reason: FinishReason) {
+    let (color, text, hover) = match reason {
+        FinishReason::Stop => (Color32::from_rgb(186, 194, 222), "⏹ stop", "The model ended its reply"),
+        FinishReason::Length => (
+            Color32::from_rgb(249, 226, 175),
+            "✂ length",
+            "Cut off at max tokens or the context size; Continue extends it",
+        ),
+        FinishReason::Cancel => (Color32::from_rgb(250, 179, 135), "🚫 cancelled", "Stopped before the model finished"),
+        FinishReason::Error => (Color32::from_rgb(243, 139, 168), "⚠ error", "The request failed; see the status bar"),
+    };
+    ui.label(RichText::new(text).small().color(color)).on_hover_text(hover);
+}
+
+/// Token counts and speed under a reply, from llama-server's timings or
+/// the OpenAI usage block
+pub fn reply_stats_label(ui: &mut egui::Ui, m: &Msg) {
+    let text = match (&m.timings, &m.usage) {
+        (Some(t), _) => format!(
+            "📊 {} prompt ({} cached) · {} generated · {:.1} tok/s",
+            t.prompt_n + t.cache_n,
+            t.cache_n,
+            t.predicted_n,
+            t.predicted_per_second
+        ),
+        (None, Some(u)) => format!("📊 {} prompt · {} generated", u.prompt_tokens, u.completion_tokens),
+        (None, None) => return,
+    };
+    let r = ui.label(RichText::new(text).small().color(Color32::from_rgb(186, 194, 222)));
+    if let Some(t) = &m.timings {
+        r.on_hover_text(format!(
+            "Prompt: {} tokens in {:.0} ms ({:.1} tok/s)\nGeneration: {} tokens in {:.0} ms ({:.1} tok/s)",
+            t.prompt_n, t.prompt_ms, t.prompt_per_second, t.predicted_n, t.predicted_ms, t.predicted_per_second
+        ));
+    }
+}
+
+/// Small status dot for the last health check of a connection
+pub fn connection_health_label(ui: &mut egui::Ui, health: Option<&ConnectionHealth>) {
+    match health {
+        None => {
+            ui.colored_label(Color32::from_rgb(186, 194, 222), "○ unchecked");
+        }
+        Some(ConnectionHealth::Checking) => {
+            ui.spinner();
+        }
+        Some(ConnectionHealth::Online(n)) => {
+            ui.colored_label(Color32::from_rgb(166, 227, 161), format!("● online ({n} models)"));
+        }
+        Some(ConnectionHealth::Offline(e)) => {
+            ui.colored_label(Color32::from_rgb(243, 139, 168), "● offline").on_hover_text(e);
+        }
+    }
+}
+
 pub fn setup_style(ctx: &egui::Context) {
     let mut style = (*ctx.style()).clone();
     style.visuals.dark_mode = true;
@@ -1,89 +1,344 @@
 use crate::models::Msg;
-use eframe::egui::{self, Align, Layout, Color32, RichText};
+use eframe::egui::{self, Color32, RichText};
 
 pub fn render_chat_panel(app: &mut crate::app::App, ui: &mut egui::Ui) {
-    ui.with_layout(Layout::top_down(Align::Min), |ui| {
-        let mut pending_truncate: Option<usize> = None;
-        for (i, m) in app.msgs.iter_mut().enumerate() {
-            ui.group(|ui| {
-                ui.horizontal(|ui| {
-                    let icon = if m.role == "user" { "👤" } else { "🤖" };
-                    ui.label(RichText::new(format!("{} {}", icon, if m.role == "user" { "You" } else { "Assistant" })).color(Color32::from_rgb(137, 180, 250)));
-                    if ui.add(crate::ui::light_button("✏️ Edit", Color32::from_rgb(137, 180, 250))).clicked() {
-                        app.editing = Some(i);
-                    }
-                    if ui.add(crate::ui::light_button("🔄 Restart from here", Color32::from_rgb(249, 226, 175))).clicked() {
-                        pending_truncate = Some(i + 1);
+    render_chat_header(app, ui);
+    ui.separator();
+    // Claim the bottom strip first so the input stays put however long the chat gets
+    egui::TopBottomPanel::bottom("chat_input").show_inside(ui, |ui| {
+        ui.add_space(4.0);
+        render_chat_input(app, ui);
+    });
+    render_messages(app, ui);
+}
+
+fn render_chat_header(app: &mut crate::app::App, ui: &mut egui::Ui) {
+    ui.horizontal(|ui| {
+        ui.label(RichText::new("🎭 Persona:").color(Color32::from_rgb(137, 180, 250)));
+        let mut persona = app.current_persona.clone();
+        egui::ComboBox::from_id_source("chat_persona")
+            .selected_text(persona.as_deref().unwrap_or("None"))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut persona, None, "None");
+                for p in &app.settings.personas {
+                    ui.selectable_value(&mut persona, Some(p.name.clone()), &p.name)
+                        .on_hover_text(&p.system_prompt);
+                }
+            });
+        let persona_changed = persona != app.current_persona;
+        if persona_changed {
+            app.select_persona(persona);
+        }
+
+        ui.separator();
+
+        ui.label(RichText::new("🎛️ Preset:").color(Color32::from_rgb(137, 180, 250)));
+        let before = app.current_preset.clone();
+        egui::ComboBox::from_id_source("chat_preset")
+            .selected_text(&app.current_preset)
+            .show_ui(ui, |ui| {
+                for p in &app.settings.sampling_presets {
+                    ui.selectable_value(&mut app.current_preset, p.name.clone(), &p.name);
+                }
+            });
+        ui.separator();
+
+        ui.label(RichText::new("🔌 Connection:").color(Color32::from_rgb(137, 180, 250)));
+        let mut connection = app.current_connection.clone();
+        egui::ComboBox::from_id_source("chat_connection")
+            .selected_text(app.active_connection().name)
+            .show_ui(ui, |ui| {
+                for c in &app.settings.connections {
+                    ui.selectable_value(&mut connection, c.name.clone(), &c.name)
+                        .on_hover_text(c.kind.label());
+                }
+            });
+        let connection_changed = connection != app.current_connection;
+        if connection_changed {
+            app.select_connection(&connection);
+        }
+        if !app.using_local_server() {
+            crate::ui::connection_health_label(ui, app.connection_health.get(&connection));
+        }
+
+        let conn = app.active_connection();
+        if let Some(models) = app.connection_models.get(&conn.name).filter(|m| !m.is_empty()) {
+            ui.label(RichText::new("🧠 Model:").color(Color32::from_rgb(137, 180, 250)));
+            let mut model = conn.model.clone();
+            egui::ComboBox::from_id_source("chat_model")
+                .selected_text(if model.is_empty() { "server default" } else { model.as_str() })
+                .show_ui(ui, |ui| {
+                    for m in models {
+                        let r = ui.selectable_value(&mut model, m.id.clone(), &m.id);
+                        if let Some(owner) = &m.owned_by {
+                            r.on_hover_text(format!("owned by {owner}"));
+                        }
                     }
                 });
-                if app.editing == Some(i) {
-                    ui.text_edit_multiline(&mut m.content);
-                    if ui.add(crate::ui::light_button("✅ Apply", Color32::from_rgb(166, 227, 161))).clicked() {
-                        app.editing = None;
-                    }
-                } else {
-                    ui.label(&m.content);
-                }
+            if model != conn.model
+                && let Some(c) = app.settings.connections.iter_mut().find(|c| c.name == conn.name)
+            {
+                c.model = model;
+                let _ = app.save_settings();
+            }
+        }
+        if ui.small_button("🔄").on_hover_text("Refresh model list").clicked() {
+            app.check_connection(&conn.name);
+        }
+
+        if (persona_changed || connection_changed || app.current_preset != before) && app.current_conversation.is_some() {
+            app.persist_chat();
+        }
+    });
+}
+
+/// Last known on-screen height of each message, keyed by content length so
+/// edited or streaming messages get measured again
+#[derive(Default)]
+pub struct MessageHeights {
+    width: f32,
+    entries: Vec<(usize, f32)>,
+}
+
+impl MessageHeights {
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Rough height for a message that has not been on screen yet
+    fn estimate(m: &Msg, width: f32) -> f32 {
+        let per_line = (width / 7.5).max(20.0) as usize;
+        let lines: usize = m.content.lines().map(|l| l.chars().count() / per_line + 1).sum();
+        48.0 + lines.max(1) as f32 * 18.0
+    }
+
+    fn sync(&mut self, msgs: &[Msg], width: f32) {
+        // Wrapping changes with the width, so start over from estimates
+        if (self.width - width).abs() > 1.0 {
+            self.width = width;
+            self.entries.clear();
+        }
+        self.entries.truncate(msgs.len());
+        for (i, m) in msgs.iter().enumerate() {
+            match self.entries.get_mut(i) {
+                Some(e) if e.0 == m.content.len() => {}
+                Some(e) => *e = (m.content.len(), Self::estimate(m, width)),
+                None => self.entries.push((m.content.len(), Self::estimate(m, width))),
+            }
+        }
+    }
+
+    /// Index of the first message reaching below `y`, and where it starts
+    fn first_visible(&self, y: f32) -> (usize, f32) {
+        let mut top = 0.0;
+        for (i, (_, h)) in self.entries.iter().enumerate() {
+            if top + h > y {
+                return (i, top);
+            }
+            top += h;
+        }
+        (self.entries.len(), top)
+    }
+
+    /// Record a measured height; true if it moved the layout
+    fn set(&mut self, i: usize, height: f32) -> bool {
+        let entry = &mut self.entries[i].1;
+        let changed = (*entry - height).abs() > 0.5;
+        *entry = height;
+        changed
+    }
+
+    fn height_from(&self, i: usize) -> f32 {
+        self.entries[i..].iter().map(|(_, h)| h).sum()
+    }
+}
+
+/// Only messages inside the viewport are laid out; the rest are stood in
+/// for by empty space of their cached height
+fn render_messages(app: &mut crate::app::App, ui: &mut egui::Ui) {
+    let mut scroll = egui::ScrollArea::vertical()
+        .auto_shrink([false, false])
+        .stick_to_bottom(true);
+    if std::mem::take(&mut app.scroll_to_bottom) {
+        scroll = scroll.vertical_scroll_offset(f32::MAX);
+    }
+    let mut action: Option<MessageAction> = None;
+    let streaming_idx = app.rx.as_ref().map(|_| app.msgs.len().saturating_sub(1));
+    scroll.show_viewport(ui, |ui, viewport| {
+        app.msg_heights.sync(&app.msgs, ui.available_width());
+        let (first, top) = app.msg_heights.first_visible(viewport.min.y);
+        ui.add_space(top);
+        let mut y = top;
+        let mut i = first;
+        let mut moved = false;
+        while i < app.msgs.len() && y < viewport.max.y {
+            let start = ui.cursor().top();
+            ui.push_id(i, |ui| {
+                render_message(app, ui, i, streaming_idx == Some(i), &mut action);
             });
+            let height = ui.cursor().top() - start;
+            moved |= app.msg_heights.set(i, height);
+            y += height;
+            i += 1;
+        }
+        ui.add_space(app.msg_heights.height_from(i));
+        // Estimates were off; lay out again with the real heights
+        if moved {
+            ui.ctx().request_repaint();
         }
-        if let Some(t) = pending_truncate {
+    });
+    match action {
+        Some(MessageAction::Truncate(t)) => {
             app.msgs.truncate(t);
+            app.markdown.truncate(t);
+            app.persist_chat();
         }
-        ui.separator();
-        if ui.text_edit_multiline(&mut app.input).changed() {
-            app.mark_activity(); // Mark activity when user types
+        Some(MessageAction::Branch(i)) => {
+            app.editing = None;
+            let content = std::mem::take(&mut app.edit_buffer);
+            app.branch_message(i, content);
         }
+        Some(MessageAction::Switch(i, delta)) => app.switch_branch(i, delta),
+        Some(MessageAction::Regenerate(i)) => app.regenerate(i),
+        Some(MessageAction::Continue) => app.continue_reply(),
+        None => {}
+    }
+}
+
+/// Changes to the chat requested from inside the message list
+enum MessageAction {
+    Truncate(usize),
+    Branch(usize),
+    Switch(usize, isize),
+    Regenerate(usize),
+    Continue,
+}
+
+fn render_message(
+    app: &mut crate::app::App,
+    ui: &mut egui::Ui,
+    i: usize,
+    streaming: bool,
+    action: &mut Option<MessageAction>,
+) {
+    let busy = app.rx.is_some();
+    let can_continue = i + 1 == app.msgs.len() && app.active_connection().kind.supports_prefill();
+    let branches = app.path.get(i).map(|&id| app.tree.siblings(id)).filter(|(_, n)| *n > 1);
+    let m = &app.msgs[i];
+    ui.group(|ui| {
+        ui.set_width(ui.available_width());
         ui.horizontal(|ui| {
-            let sending = app.rx.is_some();
-            if ui
-                .add_enabled(!sending, crate::ui::light_button("📤 Send", Color32::from_rgb(166, 227, 161)))
-                .clicked()
-            {
-                app.mark_activity(); // Mark activity when sending message
-                if !app.server_ready {
-                    app.status = "Server not ready yet".into();
+            let icon = if m.role == "user" { "👤" } else { "🤖" };
+            ui.label(RichText::new(format!("{} {}", icon, if m.role == "user" { "You" } else { "Assistant" })).color(Color32::from_rgb(137, 180, 250)));
+            if let Some(model) = &m.model {
+                ui.label(RichText::new(model).small().color(Color32::from_rgb(186, 194, 222)));
+            }
+            if let Some((pos, count)) = branches {
+                if ui.add_enabled(!busy && pos > 0, egui::Button::new("◀").small()).clicked() {
+                    *action = Some(MessageAction::Switch(i, -1));
                 }
-                let input_text = app.input.trim().to_string();
-                if !input_text.is_empty() {
-                    match app.msgs.last_mut() {
-                        Some(last) if last.role == "user" => {
-                            if !last.content.is_empty() {
-                                last.content.push_str("\n\n");
-                            }
-                            last.content.push_str(&input_text);
-                        }
-                        _ => {
-                            app.msgs.push(Msg {
-                                role: "user".into(),
-                                content: input_text.clone(),
-                            });
+                ui.label(RichText::new(format!("{}/{}", pos + 1, count)).small());
+                if ui.add_enabled(!busy && pos + 1 < count, egui::Button::new("▶").small()).clicked() {
+                    *action = Some(MessageAction::Switch(i, 1));
+                }
+            }
+            if ui.add(crate::ui::light_button("✏️ Edit", Color32::from_rgb(137, 180, 250))).clicked() {
+                app.editing = Some(i);
+                app.edit_buffer = m.content.clone();
+            }
+            if ui.add_enabled(!busy, crate::ui::light_button("🔄 Restart from here", Color32::from_rgb(249, 226, 175))).clicked() {
+                *action = Some(MessageAction::Truncate(i + 1));
+            }
+        });
+        if app.editing == Some(i) {
+            ui.text_edit_multiline(&mut app.edit_buffer);
+            ui.horizontal(|ui| {
+                let label = if m.role == "user" { "✅ Apply & resend" } else { "✅ Apply" };
+                if ui.add_enabled(!busy, crate::ui::light_button(label, Color32::from_rgb(166, 227, 161)))
+                    .on_hover_text("Kept as a new branch next to the original")
+                    .clicked()
+                {
+                    *action = Some(MessageAction::Branch(i));
+                }
+                if ui.add(crate::ui::light_button("✖ Cancel", Color32::from_rgb(243, 139, 168))).clicked() {
+                    app.editing = None;
+                }
+            });
+        } else if m.role == "assistant" {
+            let (head, tail) = app.markdown.get(i, &m.content, streaming);
+            crate::ui_markdown::render_markdown(ui, head);
+            ui.push_id("tail", |ui| crate::ui_markdown::render_markdown(ui, tail));
+            if !streaming {
+                ui.horizontal(|ui| {
+                    if let Some(reason) = m.finish {
+                        crate::ui::finish_label(ui, reason);
+                    }
+                    crate::ui::reply_stats_label(ui, m);
+                    if ui
+                        .add_enabled(!busy, egui::Button::new(RichText::new("🔁 Regenerate").small()))
+                        .on_hover_text("Ask again; this reply is kept as a branch")
+                        .clicked()
+                    {
+                        *action = Some(MessageAction::Regenerate(i));
+                    }
+                    if can_continue
+                        && ui
+                            .add_enabled(!busy, egui::Button::new(RichText::new("⏩ Continue").small()))
+                            .on_hover_text("Let the model keep writing this reply")
+                            .clicked()
+                    {
+                        *action = Some(MessageAction::Continue);
+                    }
+                });
+            }
+        } else {
+            ui.label(&m.content);
+        }
+    });
+}
+
+fn render_chat_input(app: &mut crate::app::App, ui: &mut egui::Ui) {
+    if ui.text_edit_multiline(&mut app.input).changed() {
+        app.mark_activity(); // Mark activity when user types
+    }
+    ui.horizontal(|ui| {
+        let sending = app.rx.is_some();
+        if ui
+            .add_enabled(!sending, crate::ui::light_button("📤 Send", Color32::from_rgb(166, 227, 161)))
+            .clicked()
+        {
+            app.mark_activity(); // Mark activity when sending message
+            if app.using_local_server() && !app.server_ready {
+                app.status = "Server not ready yet".into();
+            }
+            let input_text = app.input.trim().to_string();
+            if !input_text.is_empty() {
+                match app.msgs.last_mut() {
+                    Some(last) if last.role == "user" => {
+                        if !last.content.is_empty() {
+                            last.content.push_str("\n\n");
                         }
+                        last.content.push_str(&input_text);
+                    }
+                    _ => {
+                        app.msgs.push(Msg {
+                            role: "user".into(),
+                            content: input_text.clone(),
+                            ..Default::default()
+                        });
                     }
-                    app.msgs.push(Msg {
-                        role: "assistant".into(),
-                        content: String::new(),
-                    });
-                    let (tx, rx) = std::sync::mpsc::channel::<crate::models::StreamEvent>();
-                    app.rx = Some(rx);
-                    let url = app.server_url.clone();
-                    let msgs = app.msgs.clone();
-                    let model = app
-                        .served_model_id
-                        .clone()
-                        .unwrap_or_else(|| "local".into());
-                    crate::stream::stream_chat(&url, model, msgs, tx);
-                    app.input.clear();
                 }
+                app.input.clear();
+                app.start_reply();
             }
-            if sending {
-                if ui.add(crate::ui::light_button("❌ Cancel", Color32::from_rgb(243, 139, 168))).clicked() {
-                    app.rx = None;
-                    app.status = "Canceled".into();
-                }
-                ui.label(RichText::new("⚡ Generating…").color(Color32::from_rgb(249, 226, 175)));
-            } else {
-                ui.label(RichText::new("💬 Streaming; edit any message to branch.").color(Color32::from_rgb(186, 194, 222)));
+        }
+        if sending {
+            if ui.add(crate::ui::light_button("❌ Cancel", Color32::from_rgb(243, 139, 168))).clicked() {
+                app.cancel_reply();
             }
-        });
+            ui.label(RichText::new("⚡ Generating…").color(Color32::from_rgb(249, 226, 175)));
+        } else {
+            ui.label(RichText::new("💬 Streaming; edit any message to branch.").color(Color32::from_rgb(186, 194, 222)));
+        }
     });
 }
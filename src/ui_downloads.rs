@@ -0,0 +1,133 @@
+use crate::download::human_size;
+use crate::download_manager::{eta, human_duration};
+use crate::models::{DownloadKind, JobState};
+use eframe::egui::{self, Color32, RichText};
+
+pub fn render_downloads_panel(app: &mut crate::app::App, ui: &mut egui::Ui) {
+    egui::CollapsingHeader::new(RichText::new("🔍 Find models").color(Color32::from_rgb(137, 180, 250)))
+        .default_open(app.downloads.jobs.is_empty())
+        .show(ui, |ui| {
+            crate::ui_top::render_top_panel(app, ui);
+        });
+
+    ui.separator();
+
+    ui.horizontal(|ui| {
+        ui.label(RichText::new("📥 Jobs").color(Color32::from_rgb(137, 180, 250)));
+        ui.separator();
+        ui.label("Parallel downloads:");
+        if ui
+            .add(egui::DragValue::new(&mut app.settings.max_concurrent_downloads).range(1..=8))
+            .changed()
+        {
+            let _ = app.save_settings();
+        }
+        if ui.add(crate::ui::light_button("🧹 Clear finished", Color32::from_rgb(137, 180, 250))).clicked() {
+            app.downloads.clear_finished();
+        }
+    });
+
+    if app.downloads.jobs.is_empty() {
+        ui.label("No downloads yet.");
+        return;
+    }
+
+    enum Action {
+        Pause,
+        Resume,
+        Cancel,
+        Retry,
+    }
+    let mut action: Option<(u64, Action)> = None;
+
+    egui::ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+        for job in &app.downloads.jobs {
+            ui.group(|ui| {
+                ui.horizontal(|ui| {
+                    let icon = match job.kind {
+                        DownloadKind::Model => "🧠",
+                        DownloadKind::Runtime => "🖥️",
+                    };
+                    ui.label(format!("{icon} {}", job.label));
+                    let (state_txt, color) = match job.state {
+                        JobState::Queued => ("Queued", Color32::from_rgb(186, 194, 222)),
+                        JobState::Running => (job.stage, Color32::from_rgb(249, 226, 175)),
+                        JobState::Paused => ("Paused", Color32::from_rgb(137, 180, 250)),
+                        JobState::Completed => ("Completed", Color32::from_rgb(166, 227, 161)),
+                        JobState::Failed => ("Failed", Color32::from_rgb(243, 139, 168)),
+                        JobState::Cancelled => ("Cancelled", Color32::from_rgb(243, 139, 168)),
+                    };
+                    ui.colored_label(color, state_txt);
+                });
+
+                let frac = job
+                    .total
+                    .filter(|t| *t > 0)
+                    .map(|t| job.current as f32 / t as f32)
+                    .unwrap_or(0.0);
+                let mut text = format!(
+                    "{} / {}",
+                    human_size(job.current),
+                    job.total.map(human_size).unwrap_or_else(|| "?".into())
+                );
+                if job.state == JobState::Running && job.speed > 0.0 {
+                    text.push_str(&format!("  ·  {}/s", human_size(job.speed as u64)));
+                    if let Some(left) = eta(job) {
+                        text.push_str(&format!("  ·  ETA {}", human_duration(left)));
+                    }
+                }
+                ui.add(
+                    egui::ProgressBar::new(frac)
+                        .text(text)
+                        .fill(Color32::from_rgb(249, 226, 175)),
+                );
+
+                if let Some(err) = &job.error {
+                    ui.colored_label(Color32::from_rgb(243, 139, 168), err);
+                }
+
+                ui.horizontal(|ui| match job.state {
+                    JobState::Queued | JobState::Running => {
+                        if ui.add(crate::ui::light_button("⏸️ Pause", Color32::from_rgb(137, 180, 250))).clicked() {
+                            action = Some((job.id, Action::Pause));
+                        }
+                        if ui.add(crate::ui::light_button("❌ Cancel", Color32::from_rgb(243, 139, 168))).clicked() {
+                            action = Some((job.id, Action::Cancel));
+                        }
+                    }
+                    JobState::Paused => {
+                        let ready = job.stage != "pausing";
+                        if ui
+                            .add_enabled(ready, crate::ui::light_button("▶️ Resume", Color32::from_rgb(166, 227, 161)))
+                            .clicked()
+                        {
+                            action = Some((job.id, Action::Resume));
+                        }
+                        if ui.add(crate::ui::light_button("❌ Cancel", Color32::from_rgb(243, 139, 168))).clicked() {
+                            action = Some((job.id, Action::Cancel));
+                        }
+                    }
+                    JobState::Failed | JobState::Cancelled => {
+                        let ready = job.stage != "cancelling";
+                        if ui
+                            .add_enabled(ready, crate::ui::light_button("🔁 Retry", Color32::from_rgb(249, 226, 175)))
+                            .clicked()
+                        {
+                            action = Some((job.id, Action::Retry));
+                        }
+                    }
+                    JobState::Completed => {}
+                });
+            });
+        }
+    });
+
+    if let Some((id, action)) = action {
+        match action {
+            Action::Pause => app.downloads.pause(id),
+            Action::Resume => app.downloads.resume(id),
+            Action::Cancel => app.downloads.cancel(id),
+            Action::Retry => app.downloads.retry(id),
+        }
+    }
+}
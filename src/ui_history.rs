@@ -0,0 +1,78 @@
+use eframe::egui::{self, Color32, RichText};
+
+pub fn render_history_panel(app: &mut crate::app::App, ui: &mut egui::Ui) {
+    ui.heading(RichText::new("🗂️ Chats").color(Color32::from_rgb(137, 180, 250)));
+    if ui.add(crate::ui::light_button("💬 New Chat", Color32::from_rgb(166, 227, 161))).clicked() {
+        app.new_chat();
+    }
+
+    ui.separator();
+
+    if app.conversations.is_empty() {
+        ui.label("No saved chats yet.");
+        return;
+    }
+
+    let mut open: Option<String> = None;
+    let mut delete: Option<String> = None;
+    let mut commit_rename: Option<(String, String)> = None;
+
+    egui::ScrollArea::vertical().show(ui, |ui| {
+        for conv in &app.conversations {
+            let is_current = app.current_conversation.as_deref() == Some(conv.id.as_str());
+            ui.group(|ui| {
+                match &mut app.renaming {
+                    Some((id, title)) if *id == conv.id => {
+                        let resp = ui.text_edit_singleline(title);
+                        ui.horizontal(|ui| {
+                            let enter = resp.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                            if enter || ui.add(crate::ui::light_button("✅ Save", Color32::from_rgb(166, 227, 161))).clicked() {
+                                commit_rename = Some((id.clone(), title.trim().to_string()));
+                            }
+                        });
+                    }
+                    _ => {
+                        let title = RichText::new(&conv.title).color(if is_current {
+                            Color32::from_rgb(166, 227, 161)
+                        } else {
+                            Color32::from_rgb(205, 214, 244)
+                        });
+                        if ui.selectable_label(is_current, title).clicked() {
+                            open = Some(conv.id.clone());
+                        }
+                        ui.label(
+                            RichText::new(format!(
+                                "{} · {}",
+                                crate::history::relative_time(conv.updated_at),
+                                conv.model.as_deref().unwrap_or("no model")
+                            ))
+                            .small()
+                            .color(Color32::from_rgb(186, 194, 222)),
+                        );
+                        ui.horizontal(|ui| {
+                            if ui.add(crate::ui::light_button("✏️", Color32::from_rgb(137, 180, 250))).on_hover_text("Rename").clicked() {
+                                app.renaming = Some((conv.id.clone(), conv.title.clone()));
+                            }
+                            if ui.add(crate::ui::light_button("🗑️", Color32::from_rgb(243, 139, 168))).on_hover_text("Delete").clicked() {
+                                delete = Some(conv.id.clone());
+                            }
+                        });
+                    }
+                }
+            });
+        }
+    });
+
+    if let Some((id, title)) = commit_rename {
+        app.renaming = None;
+        if !title.is_empty() {
+            app.rename_conversation(&id, title);
+        }
+    }
+    if let Some(id) = open {
+        app.open_conversation(&id);
+    }
+    if let Some(id) = delete {
+        app.delete_conversation(&id);
+    }
+}
@@ -0,0 +1,188 @@
+use eframe::egui::{self, text::LayoutJob, Color32, FontId, RichText, TextFormat};
+
+const TEXT: Color32 = Color32::from_rgb(205, 214, 244);
+const ERROR: Color32 = Color32::from_rgb(243, 139, 168);
+const WARN: Color32 = Color32::from_rgb(249, 226, 175);
+const APP: Color32 = Color32::from_rgb(137, 180, 250);
+const MATCH_BG: Color32 = Color32::from_rgb(88, 91, 112);
+
+/// Filter settings of the log window and the text they produced
+pub struct LogView {
+    pub show_out: bool,
+    pub show_err: bool,
+    pub show_app: bool,
+    pub query: String,
+    pub follow: bool,
+    jump_to_end: bool,
+    key: Option<(u64, bool, bool, bool, String)>,
+    text: String,
+    /// `text` colored and with matches marked, rebuilt along with it
+    job: LayoutJob,
+    shown: usize,
+}
+
+impl Default for LogView {
+    fn default() -> Self {
+        Self {
+            show_out: true,
+            show_err: true,
+            show_app: true,
+            query: String::new(),
+            follow: true,
+            jump_to_end: false,
+            key: None,
+            text: String::new(),
+            job: LayoutJob::default(),
+            shown: 0,
+        }
+    }
+}
+
+impl LogView {
+    fn wants(&self, line: &str) -> bool {
+        let stream_ok = if line.starts_with("[OUT]") {
+            self.show_out
+        } else if line.starts_with("[ERR]") {
+            self.show_err
+        } else {
+            self.show_app
+        };
+        stream_ok && (self.query.is_empty() || line.to_ascii_lowercase().contains(&self.query.to_ascii_lowercase()))
+    }
+
+    /// Rebuild the shown text only when the log or a filter changed
+    fn refresh(&mut self, log: &[String], seq: u64) {
+        let key = (seq, self.show_out, self.show_err, self.show_app, self.query.clone());
+        if self.key.as_ref() == Some(&key) {
+            return;
+        }
+        self.text.clear();
+        self.shown = 0;
+        for line in log {
+            if !self.wants(line) {
+                continue;
+            }
+            self.text.push_str(line);
+            self.text.push('\n');
+            self.shown += 1;
+        }
+        self.job = highlight(&self.text, &self.query);
+        // Long lines scroll sideways rather than wrap
+        self.job.wrap.max_width = f32::INFINITY;
+        self.key = Some(key);
+    }
+}
+
+/// Rough severity of a line; llama-server writes everything to stderr, so
+/// the stream alone says nothing
+fn line_color(line: &str) -> Color32 {
+    let l = line.to_ascii_lowercase();
+    if l.contains("error") || l.contains("failed") || l.contains("fatal") || l.starts_with("[timeout]") {
+        ERROR
+    } else if l.contains("warn") {
+        WARN
+    } else if !l.starts_with("[out]") && !l.starts_with("[err]") {
+        APP
+    } else {
+        TEXT
+    }
+}
+
+/// Color each line by severity and mark case-insensitive matches of `query`
+fn highlight(text: &str, query: &str) -> LayoutJob {
+    let mut job = LayoutJob::default();
+    let font = FontId::monospace(11.0);
+    let query = query.to_ascii_lowercase();
+    for line in text.split_inclusive('\n') {
+        let color = line_color(line);
+        let plain = TextFormat::simple(font.clone(), color);
+        let found = TextFormat {
+            background: MATCH_BG,
+            ..plain.clone()
+        };
+        if query.is_empty() {
+            job.append(line, 0.0, plain);
+            continue;
+        }
+        // ASCII lowercasing keeps byte offsets valid in the original line
+        let lower = line.to_ascii_lowercase();
+        let mut pos = 0;
+        while let Some(i) = lower[pos..].find(&query) {
+            let start = pos + i;
+            job.append(&line[pos..start], 0.0, plain.clone());
+            job.append(&line[start..start + query.len()], 0.0, found.clone());
+            pos = start + query.len();
+        }
+        job.append(&line[pos..], 0.0, plain);
+    }
+    job
+}
+
+/// Write the shown lines next to the rotating logs, returning the path
+fn export(dir: &std::path::Path, text: &str) -> std::io::Result<std::path::PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("server-export-{}.log", crate::history::now_secs()));
+    std::fs::write(&path, text)?;
+    Ok(path)
+}
+
+pub fn render_log_viewer(app: &mut crate::app::App, ui: &mut egui::Ui) {
+    let view = &mut app.log_view;
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut view.show_out, "stdout");
+        ui.checkbox(&mut view.show_err, "stderr");
+        ui.checkbox(&mut view.show_app, "app");
+        ui.separator();
+        ui.label("🔍");
+        ui.add(egui::TextEdit::singleline(&mut view.query).hint_text("Search").desired_width(160.0));
+        if !view.query.is_empty() && ui.small_button("✖").clicked() {
+            view.query.clear();
+        }
+        ui.separator();
+        if ui.checkbox(&mut view.follow, "Auto-follow").changed() && view.follow {
+            view.jump_to_end = true;
+        }
+    });
+    view.refresh(&app.server_log, app.server_log_seq);
+    ui.horizontal(|ui| {
+        ui.label(RichText::new(format!("{} of {} lines", view.shown, app.server_log.len())).small());
+        if ui
+            .small_button("📋 Copy")
+            .on_hover_text("Copy every line shown; to copy part, select it and press Ctrl+C")
+            .clicked()
+        {
+            ui.ctx().copy_text(view.text.clone());
+        }
+        if ui.small_button("💾 Save to file").clicked() {
+            app.status = match export(app.log_file.dir(), &view.text) {
+                Ok(path) => format!("Saved log to {}", path.display()),
+                Err(e) => format!("Log save err: {e}"),
+            };
+        }
+        ui.label(RichText::new(format!("Rotating logs in {}", app.log_file.dir().display())).small())
+            .on_hover_text("server.log is the newest; server.1.log … server.4.log hold older output");
+    });
+    ui.separator();
+
+    let mut scroll = egui::ScrollArea::both()
+        .id_source("log_viewer")
+        .auto_shrink([false, false])
+        .stick_to_bottom(view.follow);
+    if std::mem::take(&mut view.jump_to_end) {
+        scroll = scroll.vertical_scroll_offset(f32::MAX);
+    }
+    scroll.show(ui, |ui| {
+        // The text never changes under the read-only editor, so the job
+        // built by `refresh` always matches it
+        let job = &view.job;
+        let mut layouter = |ui: &egui::Ui, _text: &str, _wrap_width: f32| ui.fonts(|f| f.layout_job(job.clone()));
+        // Read-only editor so a selection can span lines and be copied
+        let mut text = view.text.as_str();
+        ui.add(
+            egui::TextEdit::multiline(&mut text)
+                .font(egui::TextStyle::Monospace)
+                .desired_width(f32::INFINITY)
+                .layouter(&mut layouter),
+        );
+    });
+}
@@ -0,0 +1,172 @@
+use crate::markdown::{Block, Span};
+use eframe::egui::{self, text::LayoutJob, Color32, FontId, RichText, Stroke, TextFormat};
+use egui_extras::syntax_highlighting::{highlight, CodeTheme};
+
+const TEXT: Color32 = Color32::from_rgb(205, 214, 244);
+const LINK: Color32 = Color32::from_rgb(137, 180, 250);
+const CODE_BG: Color32 = Color32::from_rgb(35, 36, 48);
+const CODE_FG: Color32 = Color32::from_rgb(250, 179, 135);
+
+pub fn render_markdown(ui: &mut egui::Ui, blocks: &[Block]) {
+    for (i, block) in blocks.iter().enumerate() {
+        ui.push_id(i, |ui| render_block(ui, block));
+    }
+}
+
+fn render_block(ui: &mut egui::Ui, block: &Block) {
+    match block {
+        Block::Heading(level, spans) => {
+            let size = match level {
+                1 => 22.0,
+                2 => 19.0,
+                3 => 17.0,
+                _ => 15.0,
+            };
+            ui.add_space(4.0);
+            render_spans(ui, spans, size, true);
+        }
+        Block::Paragraph(spans) => render_spans(ui, spans, 14.0, false),
+        Block::Code { lang, code } => render_code(ui, lang, code),
+        Block::List { start, items } => {
+            for (n, item) in items.iter().enumerate() {
+                let marker = match start {
+                    Some(first) => format!("{}.", first + n as u64),
+                    None => "•".to_string(),
+                };
+                ui.horizontal_top(|ui| {
+                    ui.label(RichText::new(marker).color(LINK));
+                    ui.vertical(|ui| {
+                        ui.push_id(n, |ui| render_markdown(ui, item));
+                    });
+                });
+            }
+        }
+        Block::Quote(inner) => {
+            egui::Frame::none()
+                .stroke(Stroke::new(1.0, Color32::from_rgb(88, 91, 112)))
+                .inner_margin(egui::Margin::symmetric(8.0, 4.0))
+                .show(ui, |ui| render_markdown(ui, inner));
+        }
+        Block::Table { header, rows } => {
+            egui::Grid::new("md_table").striped(true).show(ui, |ui| {
+                for cell in header {
+                    render_spans(ui, cell, 14.0, true);
+                }
+                ui.end_row();
+                for row in rows {
+                    for cell in row {
+                        render_spans(ui, cell, 14.0, false);
+                    }
+                    ui.end_row();
+                }
+            });
+        }
+        Block::Rule => {
+            ui.separator();
+        }
+    }
+}
+
+fn text_format(span: &Span, size: f32, bold: bool) -> TextFormat {
+    let mut format = TextFormat {
+        font_id: if span.code { FontId::monospace(size - 1.0) } else { FontId::proportional(size) },
+        color: if span.link.is_some() {
+            LINK
+        } else if span.code {
+            CODE_FG
+        } else if bold || span.bold {
+            Color32::WHITE
+        } else {
+            TEXT
+        },
+        italics: span.italic,
+        ..Default::default()
+    };
+    if span.code {
+        format.background = CODE_BG;
+    }
+    if span.strike {
+        format.strikethrough = Stroke::new(1.0, TEXT);
+    }
+    if span.link.is_some() {
+        format.underline = Stroke::new(1.0, LINK);
+    }
+    format
+}
+
+/// Plain runs are laid out as one wrapped job; runs with links are placed
+/// word by word so each link stays clickable
+fn render_spans(ui: &mut egui::Ui, spans: &[Span], size: f32, bold: bool) {
+    if spans.iter().all(|s| s.link.is_none()) {
+        let mut job = LayoutJob::default();
+        for span in spans {
+            job.append(&span.text, 0.0, text_format(span, size, bold));
+        }
+        job.wrap.max_width = ui.available_width();
+        ui.label(job);
+        return;
+    }
+    ui.horizontal_wrapped(|ui| {
+        ui.spacing_mut().item_spacing.x = 0.0;
+        for span in spans {
+            for word in span.text.split_inclusive(' ') {
+                let mut job = LayoutJob::default();
+                job.append(word, 0.0, text_format(span, size, bold));
+                match &span.link {
+                    Some(url) => {
+                        if ui.add(egui::Label::new(job).sense(egui::Sense::click()))
+                            .on_hover_text(url)
+                            .on_hover_cursor(egui::CursorIcon::PointingHand)
+                            .clicked()
+                        {
+                            ui.ctx().open_url(egui::OpenUrl::new_tab(url));
+                        }
+                    }
+                    None => {
+                        ui.label(job);
+                    }
+                }
+            }
+        }
+    });
+}
+
+/// syntect knows languages by file extension; map the usual fence names
+fn syntax_name(lang: &str) -> &str {
+    match lang.to_ascii_lowercase().as_str() {
+        "rust" => "rs",
+        "python" | "py3" => "py",
+        "javascript" | "jsx" | "typescript" | "ts" | "tsx" => "js",
+        "bash" | "shell" | "zsh" | "console" => "sh",
+        "c++" => "cpp",
+        "csharp" | "c#" => "cs",
+        "ruby" => "rb",
+        "markdown" => "md",
+        "yml" => "yaml",
+        "" => "txt",
+        _ => lang,
+    }
+}
+
+fn render_code(ui: &mut egui::Ui, lang: &str, code: &str) {
+    egui::Frame::none()
+        .fill(Color32::from_rgb(24, 24, 37))
+        .rounding(4.0)
+        .inner_margin(6.0)
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label(RichText::new(if lang.is_empty() { "code" } else { lang }).small().color(LINK));
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.small_button("📋 Copy").clicked() {
+                        ui.ctx().copy_text(code.to_string());
+                    }
+                });
+            });
+            // Highlighting is memoized by egui_extras, so only a block that
+            // is still growing gets re-highlighted
+            let job = highlight(ui.ctx(), &CodeTheme::dark(), code, syntax_name(lang));
+            egui::ScrollArea::horizontal().show(ui, |ui| {
+                ui.add(egui::Label::new(job).selectable(true).extend());
+            });
+        });
+}
@@ -1,12 +1,13 @@
-use crate::models::DownloadedModel;
+use crate::models::{DownloadedModel, GgufInfo, SystemMemory};
 use eframe::egui::{self, Color32, RichText};
-use std::{fs, process::Command, path::PathBuf};
+use std::{fs, process::Command};
 
 pub fn render_downloaded_models(app: &mut crate::app::App, ui: &mut egui::Ui) {
     ui.collapsing(RichText::new("📁 Downloaded models").color(Color32::from_rgb(137, 180, 250)), |ui| {
         if app.downloaded.is_empty() {
             ui.label("No models downloaded yet.");
         }
+        ui.small(memory_summary(&app.system_memory));
         egui::ScrollArea::vertical()
             .max_height(160.0)
             .show(ui, |ui| {
@@ -15,23 +16,171 @@ pub fn render_downloaded_models(app: &mut crate::app::App, ui: &mut egui::Ui) {
                     ui.group(|ui| {
                         ui.horizontal(|ui| {
                             let size_txt = item.size.map(crate::download::human_size).unwrap_or("?".into());
-                            ui.label(format!("{}  ({})", item.file_name, size_txt));
-                            if ui.add(crate::ui::light_button("✅ Use", Color32::from_rgb(166, 227, 161))).clicked() {
-                                app.model_file = item.file_name.clone();
-                                app.model_repo = "(local)".into();
-                                app.model_path = Some(item.path.clone());
-                                app.status = "Selected local model".into();
+                            let complete = item.parts.len() as u32 >= item.expected_parts;
+                            if item.expected_parts > 1 {
+                                let name = crate::shards::parse_shard(&item.file_name)
+                                    .map(|s| s.base)
+                                    .unwrap_or_else(|| item.file_name.clone());
+                                ui.label(format!("{}  ({}, {} parts)", name, size_txt, item.expected_parts));
+                            } else {
+                                ui.label(format!("{}  ({})", item.file_name, size_txt));
+                            }
+                            if !complete {
+                                ui.colored_label(
+                                    Color32::from_rgb(243, 139, 168),
+                                    format!("⚠ incomplete {}/{}", item.parts.len(), item.expected_parts),
+                                );
+                            }
+                            let first_present = crate::shards::is_first_or_single(&item.file_name);
+                            if ui
+                                .add_enabled(complete && first_present, crate::ui::light_button("✅ Use", Color32::from_rgb(166, 227, 161)))
+                                .clicked()
+                            {
+                                app.switch_model(item.path.clone());
+                            }
+                            let assigned = app.settings.model_profiles.get(&item.file_name).cloned();
+                            let mut profile = assigned.clone();
+                            egui::ComboBox::from_id_source(("model_profile", &item.file_name))
+                                .selected_text(profile.as_deref().unwrap_or("Default profile"))
+                                .show_ui(ui, |ui| {
+                                    ui.selectable_value(&mut profile, None, "Default profile");
+                                    for p in &app.settings.launch_profiles {
+                                        ui.selectable_value(&mut profile, Some(p.name.clone()), &p.name);
+                                    }
+                                });
+                            if profile != assigned {
+                                match profile {
+                                    Some(p) => {
+                                        app.settings.model_profiles.insert(item.file_name.clone(), p);
+                                    }
+                                    None => {
+                                        app.settings.model_profiles.remove(&item.file_name);
+                                    }
+                                }
+                                let _ = app.save_settings();
                             }
                             if ui.add(crate::ui::light_button("🗑️ Delete", Color32::from_rgb(243, 139, 168))).clicked() {
-                                let _ = fs::remove_file(&item.path);
+                                for part in &item.parts {
+                                    let _ = fs::remove_file(part);
+                                }
                                 crate::scan::scan_downloaded_models(app);
                             }
                             if ui.add(crate::ui::light_button("📂 Reveal", Color32::from_rgb(137, 180, 250))).clicked() {
                                 let _ = Command::new("explorer").arg(&item.path).spawn();
                             }
+                            let open = app.info_model.as_ref() == Some(&item.path);
+                            if ui.add(crate::ui::light_button("ℹ️ Info", Color32::from_rgb(249, 226, 175))).clicked() {
+                                app.info_model = if open { None } else { Some(item.path.clone()) };
+                            }
                         });
+                        if let Ok(info) = &item.info {
+                            let profile = app.launch_profile_for(&item.path);
+                            let est = app.estimate_fit(info, item.size.unwrap_or(0), &profile);
+                            ui.horizontal(|ui| {
+                                ui.small(model_summary(info));
+                                crate::ui::fit_label(ui, &est);
+                            });
+                        }
+                        if app.info_model.as_ref() == Some(&item.path) {
+                            render_model_info(ui, &item);
+                        }
                     });
                 }
             });
     });
 }
+
+pub fn memory_summary(mem: &SystemMemory) -> String {
+    let ram = format!(
+        "RAM {} ({} free)",
+        crate::download::human_size(mem.ram_total),
+        crate::download::human_size(mem.ram_available)
+    );
+    match (&mem.gpu_name, mem.vram_total) {
+        (Some(name), Some(v)) if !mem.unified => format!("{ram} · {name} {}", crate::download::human_size(v)),
+        (Some(name), _) => format!("{ram} · {name}"),
+        _ => format!("{ram} · no GPU memory detected"),
+    }
+}
+
+/// One-line overview such as "llama · 8.03B · Q4_K_M · 8192 ctx"
+fn model_summary(info: &GgufInfo) -> String {
+    let mut parts = vec![];
+    if let Some(a) = &info.architecture {
+        parts.push(a.clone());
+    }
+    if let Some(n) = info.parameter_count {
+        parts.push(crate::gguf::human_params(n));
+    }
+    if let Some(q) = &info.file_type {
+        parts.push(q.clone());
+    }
+    if let Some(c) = info.context_length {
+        parts.push(format!("{c} ctx"));
+    }
+    parts.join(" · ")
+}
+
+fn render_model_info(ui: &mut egui::Ui, item: &DownloadedModel) {
+    let info = match &item.info {
+        Ok(info) => info,
+        Err(e) => {
+            ui.colored_label(Color32::from_rgb(243, 139, 168), format!("Could not read GGUF header: {e}"));
+            return;
+        }
+    };
+    let opt = |v: Option<u64>| v.map(|v| v.to_string()).unwrap_or("?".into());
+    egui::Grid::new(("gguf_info", &item.file_name))
+        .num_columns(2)
+        .striped(true)
+        .show(ui, |ui| {
+            let rows = [
+                ("Name", info.name.clone().unwrap_or("?".into())),
+                ("Architecture", info.architecture.clone().unwrap_or("?".into())),
+                ("Parameters", info.parameter_count.map(crate::gguf::human_params).unwrap_or("?".into())),
+                ("Quantization", info.file_type.clone().unwrap_or("?".into())),
+                ("Context length", opt(info.context_length)),
+                ("Layers", opt(info.block_count)),
+                ("Embedding size", opt(info.embedding_length)),
+                ("Attention heads", format!("{} (KV {})", opt(info.head_count), opt(info.head_count_kv))),
+                ("Tokenizer", info.tokenizer.clone().unwrap_or("?".into())),
+                ("Vocabulary", opt(info.vocab_size)),
+                ("Tensors", info.tensors.len().to_string()),
+                ("Weights", crate::download::human_size(info.tensors.iter().map(|t| t.bytes).sum())),
+                ("GGUF version", info.version.to_string()),
+            ];
+            for (k, v) in rows {
+                ui.label(k);
+                ui.label(v);
+                ui.end_row();
+            }
+        });
+    match &info.chat_template {
+        Some(t) => {
+            ui.collapsing("Chat template", |ui| {
+                let mut text = t.as_str();
+                ui.add(
+                    egui::TextEdit::multiline(&mut text)
+                        .code_editor()
+                        .desired_rows(6)
+                        .desired_width(f32::INFINITY),
+                );
+            });
+        }
+        None => {
+            ui.small("No chat template embedded");
+        }
+    }
+    ui.collapsing(format!("All metadata ({} keys)", info.metadata.len()), |ui| {
+        egui::Grid::new(("gguf_kv", &item.file_name))
+            .num_columns(2)
+            .striped(true)
+            .show(ui, |ui| {
+                for (k, v) in &info.metadata {
+                    ui.monospace(k);
+                    ui.label(v);
+                    ui.end_row();
+                }
+            });
+    });
+}
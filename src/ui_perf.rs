@@ -0,0 +1,178 @@
+use eframe::egui::{self, Color32, Pos2, RichText, Stroke};
+
+const CHART_BG: Color32 = Color32::from_rgb(24, 24, 37);
+const GRID: Color32 = Color32::from_rgb(49, 50, 68);
+const DIM: Color32 = Color32::from_rgb(186, 194, 222);
+
+/// Dashboard on the left, raw llama-server output on the right
+pub fn render_server_panel(app: &mut crate::app::App, ui: &mut egui::Ui) {
+    ui.columns(2, |cols| {
+        egui::ScrollArea::vertical()
+            .id_source("perf_charts")
+            .show(&mut cols[0], |ui| render_dashboard(app, ui));
+        render_server_log(app, &mut cols[1]);
+    });
+}
+
+fn render_dashboard(app: &mut crate::app::App, ui: &mut egui::Ui) {
+    ui.heading(RichText::new("📈 Performance").color(Color32::from_rgb(137, 180, 250)));
+    let Some((url, _)) = app.perf_endpoint() else {
+        ui.label(RichText::new("Start the server or pick a llama-server connection to see live stats.").color(DIM));
+        return;
+    };
+    ui.horizontal(|ui| {
+        ui.label(RichText::new(url).small().color(DIM));
+        match app.perf.last.as_ref().map(|s| &s.health) {
+            Some(Ok(())) => {
+                ui.colored_label(Color32::from_rgb(166, 227, 161), "● healthy");
+            }
+            Some(Err(e)) => {
+                ui.colored_label(Color32::from_rgb(249, 226, 175), format!("● {e}"));
+            }
+            None => {
+                ui.spinner();
+            }
+        }
+    });
+    if app.using_local_server()
+        && let Some(model) = &app.model_path
+    {
+        let args = crate::server::launch_args(&app.launch_profile_for(model), app.backend).join(" ");
+        ui.label(RichText::new(format!("Launch args: {args}")).small().color(DIM));
+    }
+    if let Some(Err(e)) = app.perf.last.as_ref().map(|s| &s.metrics) {
+        ui.colored_label(
+            Color32::from_rgb(250, 179, 135),
+            format!("/metrics unavailable ({e}); token rates need llama-server started with --metrics"),
+        );
+    }
+    ui.separator();
+
+    let points: Vec<_> = app.perf.points.iter().copied().collect();
+    let series = |f: &dyn Fn(&crate::metrics::PerfPoint) -> Option<f64>| -> Vec<(f64, f64)> {
+        points.iter().filter_map(|p| Some((p.t, f(p)?))).collect()
+    };
+    ui.label(
+        RichText::new("Speeds are reported when a request finishes; each point is one or more finished requests")
+            .small()
+            .color(DIM),
+    );
+    chart(
+        ui,
+        "Prompt processing",
+        &series(&|p| p.prompt_tps),
+        None,
+        Color32::from_rgb(137, 180, 250),
+        |v| format!("{v:.0} tok/s"),
+    );
+    chart(
+        ui,
+        "Generation",
+        &series(&|p| p.gen_tps),
+        None,
+        Color32::from_rgb(166, 227, 161),
+        |v| format!("{v:.1} tok/s"),
+    );
+    let kv = series(&|p| p.kv_used.map(|r| r * 100.0));
+    if kv.is_empty() && !points.is_empty() {
+        ui.label(RichText::new("KV cache usage is not reported by this llama-server build").small().color(DIM));
+    } else {
+        chart(ui, "KV cache", &kv, Some(100.0), Color32::from_rgb(250, 179, 135), |v| format!("{v:.0}%"));
+    }
+    let total_slots = app.perf.last.as_ref().and_then(|s| s.total_slots);
+    chart(
+        ui,
+        &match total_slots {
+            Some(n) => format!("Active slots (of {n})"),
+            None => "Active slots".to_string(),
+        },
+        &series(&|p| Some(p.busy_slots)),
+        total_slots.map(|n| n.max(1) as f64),
+        Color32::from_rgb(203, 166, 247),
+        |v| format!("{v:.0}"),
+    );
+}
+
+/// Line chart of `(seconds, value)` points, painted by hand. The y axis
+/// starts at zero and ends at `y_max` or a little above the highest value.
+fn chart(ui: &mut egui::Ui, title: &str, points: &[(f64, f64)], y_max: Option<f64>, color: Color32, fmt: impl Fn(f64) -> String) {
+    ui.horizontal(|ui| {
+        ui.label(RichText::new(title).color(color));
+        if let Some((_, v)) = points.last() {
+            ui.label(RichText::new(fmt(*v)).strong());
+        }
+    });
+    let size = egui::vec2(ui.available_width(), 80.0);
+    let (response, painter) = ui.allocate_painter(size, egui::Sense::hover());
+    let rect = response.rect;
+    painter.rect_filled(rect, 4.0, CHART_BG);
+    for frac in [0.25, 0.5, 0.75] {
+        let y = rect.bottom() - rect.height() * frac;
+        painter.hline(rect.x_range(), y, Stroke::new(1.0, GRID));
+    }
+    if points.is_empty() {
+        return;
+    }
+    let peak = points.iter().map(|p| p.1).fold(0.0, f64::max);
+    let top = y_max.unwrap_or(peak * 1.1).max(1e-6);
+    let (t0, t1) = (points[0].0, points[points.len() - 1].0);
+    let span = (t1 - t0).max(1.0);
+    let to_screen = |(t, v): (f64, f64)| {
+        Pos2::new(
+            rect.left() + ((t - t0) / span) as f32 * rect.width(),
+            rect.bottom() - (v / top).clamp(0.0, 1.0) as f32 * rect.height(),
+        )
+    };
+    let line: Vec<Pos2> = points.iter().map(|p| to_screen(*p)).collect();
+    painter.add(egui::Shape::line(line, Stroke::new(1.5, color)));
+    painter.text(
+        rect.left_top() + egui::vec2(4.0, 2.0),
+        egui::Align2::LEFT_TOP,
+        fmt(top),
+        egui::FontId::proportional(10.0),
+        DIM,
+    );
+    painter.text(
+        rect.left_bottom() + egui::vec2(4.0, -2.0),
+        egui::Align2::LEFT_BOTTOM,
+        format!("{:.0}s", span),
+        egui::FontId::proportional(10.0),
+        DIM,
+    );
+
+    // Value of the sample under the pointer
+    if let Some(pos) = response.hover_pos() {
+        let t = t0 + ((pos.x - rect.left()) / rect.width()) as f64 * span;
+        if let Some(p) = points.iter().min_by(|a, b| (a.0 - t).abs().total_cmp(&(b.0 - t).abs())) {
+            let at = to_screen(*p);
+            painter.vline(at.x, rect.y_range(), Stroke::new(1.0, DIM));
+            painter.circle_filled(at, 3.0, color);
+            painter.text(
+                rect.right_top() + egui::vec2(-4.0, 2.0),
+                egui::Align2::RIGHT_TOP,
+                format!("{} · {:.0}s ago", fmt(p.1), t1 - p.0),
+                egui::FontId::proportional(11.0),
+                Color32::WHITE,
+            );
+        }
+    }
+    ui.add_space(6.0);
+}
+
+fn render_server_log(app: &mut crate::app::App, ui: &mut egui::Ui) {
+    ui.horizontal(|ui| {
+        ui.heading(RichText::new("📜 Server log").color(Color32::from_rgb(137, 180, 250)));
+        if ui.small_button("🔍 Filter & search").clicked() {
+            app.show_logs = true;
+        }
+    });
+    egui::ScrollArea::vertical()
+        .id_source("server_log")
+        .auto_shrink([false, false])
+        .stick_to_bottom(true)
+        .show(ui, |ui| {
+            for line in &app.server_log {
+                ui.label(RichText::new(line).monospace().size(11.0));
+            }
+        });
+}
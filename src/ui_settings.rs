@@ -1,4 +1,4 @@
-use crate::models::{Backend, RuntimeInfo, ServerStatus};
+use crate::models::{Backend, Connection, ConnectionKind, LaunchProfile, Persona, RuntimeSource, SamplingPreset, ServerStatus, KV_CACHE_TYPES};
 use eframe::egui::{self, Color32, RichText};
 
 pub fn render_settings_panel(app: &mut crate::app::App, ui: &mut egui::Ui) {
@@ -8,36 +8,7 @@ pub fn render_settings_panel(app: &mut crate::app::App, ui: &mut egui::Ui) {
 
     // Runtime Settings
     ui.collapsing(RichText::new("🖥️ Runtime").color(Color32::from_rgb(166, 227, 161)), |ui| {
-        ui.label("Available Runtimes:");
-        for runtime in &app.available_runtimes {
-            let is_selected = app.current_runtime.as_ref()
-                .map(|r| r.name == runtime.name)
-                .unwrap_or(false);
-
-            let mut selected = is_selected;
-            if ui.checkbox(&mut selected, &runtime.name).changed() {
-                if selected {
-                    app.current_runtime = Some(runtime.clone());
-                    app.server_bin = Some(runtime.path.clone());
-                    app.settings.default_runtime = Some(runtime.name.clone());
-                    let _ = app.save_settings();
-                    app.status = format!("Runtime selected: {}", runtime.name);
-                } else {
-                    app.current_runtime = None;
-                    app.server_bin = None;
-                    app.settings.default_runtime = None;
-                    let _ = app.save_settings();
-                    app.status = "No runtime selected".into();
-                }
-            }
-        }
-
-        if app.available_runtimes.is_empty() {
-            ui.colored_label(Color32::from_rgb(249, 226, 175), "No runtimes detected");
-            if ui.add(crate::ui::light_button("⬇️ Download Runtime", Color32::from_rgb(166, 227, 161))).clicked() {
-                let _ = crate::runtime::ensure_runtime(app);
-            }
-        }
+        render_runtimes(app, ui);
     });
 
     ui.separator();
@@ -56,6 +27,26 @@ pub fn render_settings_panel(app: &mut crate::app::App, ui: &mut egui::Ui) {
             }
         });
 
+        if ui.checkbox(&mut app.settings.auto_restart_server, "Restart server after a crash").changed() {
+            let _ = app.save_settings();
+        }
+        if app.settings.auto_restart_server {
+            ui.horizontal(|ui| {
+                ui.label("Max restart attempts:");
+                if ui.add(egui::DragValue::new(&mut app.settings.max_restart_attempts).range(1..=10)).changed() {
+                    let _ = app.save_settings();
+                }
+            });
+        }
+
+        ui.horizontal(|ui| {
+            ui.label("Port (0 = auto):");
+            if ui.add(egui::DragValue::new(&mut app.settings.server_port)).changed() {
+                let _ = app.save_settings();
+            }
+        });
+        ui.label(RichText::new(format!("URL: {}", app.server_url)).small().color(Color32::from_rgb(186, 194, 222)));
+
         ui.separator();
 
         // Server Status
@@ -67,28 +58,49 @@ pub fn render_settings_panel(app: &mut crate::app::App, ui: &mut egui::Ui) {
                     app.ensure_server_running();
                 }
             }
+            ServerStatus::Switching(name) => {
+                ui.colored_label(Color32::from_rgb(249, 226, 175), format!("🔄 Loading {name}..."));
+                if ui.add(crate::ui::light_button("⏹️ Stop Server", Color32::from_rgb(243, 139, 168))).clicked() {
+                    app.stop_server();
+                }
+            }
             ServerStatus::Starting => {
                 ui.colored_label(Color32::from_rgb(249, 226, 175), "⏳ Starting...");
+                if ui.add(crate::ui::light_button("⏹️ Stop Server", Color32::from_rgb(243, 139, 168))).clicked() {
+                    app.stop_server();
+                }
             }
             ServerStatus::Running => {
                 ui.colored_label(Color32::from_rgb(166, 227, 161), "🟢 Running");
                 if ui.add(crate::ui::light_button("⏹️ Stop Server", Color32::from_rgb(243, 139, 168))).clicked() {
-                    if let Some(mut child) = app.server_child.take() {
-                        let _ = child.kill();
-                        app.server_ready = false;
-                        app.server_status = ServerStatus::Stopped;
-                        app.status = "Server stopped".into();
-                    }
+                    app.stop_server();
                 }
             }
             ServerStatus::Error(err) => {
                 ui.colored_label(Color32::from_rgb(243, 139, 168), format!("❌ Error: {}", err));
+                if ui.add(crate::ui::light_button("🔁 Retry", Color32::from_rgb(249, 226, 175))).clicked() {
+                    app.launch_server();
+                }
             }
         }
     });
 
     ui.separator();
 
+    // Connections
+    ui.collapsing(RichText::new("🔌 Connections").color(Color32::from_rgb(166, 227, 161)), |ui| {
+        render_connections(app, ui);
+    });
+
+    ui.separator();
+
+    // Launch profiles
+    ui.collapsing(RichText::new("🧰 Launch Profiles").color(Color32::from_rgb(166, 227, 161)), |ui| {
+        render_launch_profiles(app, ui);
+    });
+
+    ui.separator();
+
     // Chat Settings
     ui.collapsing(RichText::new("💬 Chat").color(Color32::from_rgb(249, 226, 175)), |ui| {
         ui.horizontal(|ui| {
@@ -103,6 +115,20 @@ pub fn render_settings_panel(app: &mut crate::app::App, ui: &mut egui::Ui) {
 
     ui.separator();
 
+    // Sampling presets
+    ui.collapsing(RichText::new("🎛️ Sampling").color(Color32::from_rgb(249, 226, 175)), |ui| {
+        render_sampling_presets(app, ui);
+    });
+
+    ui.separator();
+
+    // Personas
+    ui.collapsing(RichText::new("🎭 Personas").color(Color32::from_rgb(249, 226, 175)), |ui| {
+        render_personas(app, ui);
+    });
+
+    ui.separator();
+
     // Actions
     ui.horizontal(|ui| {
         if ui.add(crate::ui::light_button("💾 Save Settings", Color32::from_rgb(166, 227, 161))).clicked() {
@@ -119,3 +145,805 @@ pub fn render_settings_panel(app: &mut crate::app::App, ui: &mut egui::Ui) {
         }
     });
 }
+
+fn render_runtimes(app: &mut crate::app::App, ui: &mut egui::Ui) {
+    ui.label("Available Runtimes:");
+    let mut select = None;
+    let mut delete = None;
+    for runtime in &app.available_runtimes {
+        let is_selected = app.current_runtime.as_ref()
+            .map(|r| r.name == runtime.name)
+            .unwrap_or(false);
+
+        ui.horizontal(|ui| {
+            let mut selected = is_selected;
+            let backend = match runtime.backend {
+                Backend::Auto => "?",
+                b => crate::runtime::backend_slug(b),
+            };
+            let label = match runtime.source {
+                RuntimeSource::Managed if runtime.backend == Backend::Auto => {
+                    format!("{} ({})", runtime.name, runtime.version)
+                }
+                RuntimeSource::Managed => format!("{} · {}", runtime.version, backend),
+                RuntimeSource::Path => format!("{} · {} · PATH", runtime.version, backend),
+                RuntimeSource::System => format!("{} · {} · system", runtime.version, backend),
+                RuntimeSource::Custom => format!("{} · {} · custom", runtime.version, backend),
+            };
+            let mut hover = runtime.path.display().to_string();
+            if !runtime.build_info.is_empty() {
+                hover = format!("{hover}\n{}", runtime.build_info);
+            }
+            if ui.checkbox(&mut selected, label).on_hover_text(hover).changed() {
+                select = Some(selected.then(|| runtime.name.clone()));
+            }
+            if is_selected && app.settings.runtime_pinned {
+                ui.label("📌");
+            }
+            if runtime.source == RuntimeSource::Managed
+                && ui.add(crate::ui::light_button("🗑️", Color32::from_rgb(243, 139, 168)))
+                    .on_hover_text("Delete this runtime")
+                    .clicked()
+            {
+                delete = Some(runtime.name.clone());
+            }
+        });
+    }
+    match select {
+        Some(Some(name)) => app.select_runtime(&name),
+        Some(None) => {
+            app.current_runtime = None;
+            app.server_bin = None;
+            app.settings.default_runtime = None;
+            let _ = app.save_settings();
+            app.status = "No runtime selected".into();
+        }
+        None => {}
+    }
+    if let Some(name) = delete
+        && let Err(e) = app.delete_runtime(&name)
+    {
+        app.status = format!("Delete failed: {e}");
+    }
+
+    render_custom_runtime_paths(app, ui);
+
+    if app.available_runtimes.is_empty() {
+        ui.colored_label(Color32::from_rgb(249, 226, 175), "No runtimes detected");
+        if ui.add(crate::ui::light_button("⬇️ Download Runtime", Color32::from_rgb(166, 227, 161))).clicked()
+            && let Err(e) = crate::runtime::ensure_runtime(app)
+        {
+            app.status = format!("Runtime err: {e}");
+        }
+        return;
+    }
+
+    ui.horizontal(|ui| {
+        if ui.add_enabled(app.current_runtime.is_some(), egui::Checkbox::new(&mut app.settings.runtime_pinned, "📌 Pin version"))
+            .on_hover_text("Keep this build selected when newer ones are installed")
+            .changed()
+        {
+            let _ = app.save_settings();
+        }
+        if ui.add(crate::ui::light_button("⬆️ Upgrade", Color32::from_rgb(166, 227, 161)))
+            .on_hover_text("Install the latest llama.cpp release for this backend")
+            .clicked()
+        {
+            app.settings.runtime_pinned = false;
+            let _ = app.save_settings();
+            if let Err(e) = crate::runtime::ensure_runtime(app) {
+                app.status = format!("Runtime err: {e}");
+            }
+        }
+        let previous = app.previous_runtime().map(|r| r.name.clone());
+        if ui.add_enabled(previous.is_some(), crate::ui::light_button("↩️ Roll back", Color32::from_rgb(249, 226, 175)))
+            .on_hover_text("Switch to the previous installed build and pin it")
+            .clicked()
+            && let Some(name) = previous
+        {
+            app.select_runtime(&name);
+            app.settings.runtime_pinned = true;
+            let _ = app.save_settings();
+        }
+    });
+
+    ui.separator();
+    ui.horizontal(|ui| {
+        ui.label("Check for updates every (hours, 0 = never):");
+        if ui.add(egui::DragValue::new(&mut app.settings.update_check_hours).range(0..=720)).changed() {
+            let _ = app.save_settings();
+        }
+    });
+    ui.horizontal(|ui| {
+        if app.update_rx.is_some() {
+            ui.spinner();
+            ui.label("Checking…");
+        } else if ui.add(crate::ui::light_button("🔄 Check now", Color32::from_rgb(137, 180, 250))).clicked() {
+            app.check_runtime_updates(true);
+        }
+        if let Some(tag) = app.runtime_update().map(|r| r.tag_name.clone()) {
+            if ui.link(format!("{tag} available")).clicked() {
+                app.show_release_notes = true;
+            }
+        } else if let Some(rel) = &app.latest_release {
+            ui.label(format!("Up to date (latest {})", rel.tag_name));
+        }
+    });
+    if app.settings.last_update_check > 0 {
+        ui.small(format!(
+            "Last checked {}",
+            crate::history::relative_time(app.settings.last_update_check)
+        ));
+    }
+}
+
+/// Extra places to look for llama-server, e.g. a local source build
+fn render_custom_runtime_paths(app: &mut crate::app::App, ui: &mut egui::Ui) {
+    ui.label("Custom runtime paths:");
+    let mut remove = None;
+    for (i, p) in app.settings.custom_runtime_paths.iter().enumerate() {
+        ui.horizontal(|ui| {
+            ui.small(p.display().to_string());
+            if ui.small_button("✖").on_hover_text("Remove").clicked() {
+                remove = Some(i);
+            }
+        });
+    }
+    if let Some(i) = remove {
+        app.settings.custom_runtime_paths.remove(i);
+        let _ = app.save_settings();
+        app.detect_runtimes();
+    }
+    ui.horizontal(|ui| {
+        ui.add(
+            egui::TextEdit::singleline(&mut app.custom_runtime_input)
+                .hint_text("llama-server binary or folder")
+                .desired_width(180.0),
+        );
+        if ui.add(crate::ui::light_button("➕ Add", Color32::from_rgb(166, 227, 161))).clicked() {
+            let path = std::path::PathBuf::from(app.custom_runtime_input.trim());
+            if path.as_os_str().is_empty() || !path.exists() {
+                app.status = format!("Path not found: {}", path.display());
+            } else if !app.settings.custom_runtime_paths.contains(&path) {
+                app.settings.custom_runtime_paths.push(path);
+                app.custom_runtime_input.clear();
+                let _ = app.save_settings();
+                app.detect_runtimes();
+                app.status = format!("Found {} runtime(s)", app.available_runtimes.len());
+            }
+        }
+    });
+}
+
+/// Notes of the newest release with a one-click install for the backend in use
+pub fn render_release_notes(app: &mut crate::app::App, ui: &mut egui::Ui) {
+    let Some(rel) = app.latest_release.clone() else {
+        ui.label("No release information yet.");
+        return;
+    };
+    let be = app.update_backend();
+    ui.heading(format!("llama.cpp {}", rel.tag_name));
+    ui.horizontal(|ui| {
+        if let Some(date) = rel.published_at.get(..10) {
+            ui.label(format!("Published {date}"));
+        }
+        if !rel.html_url.is_empty() {
+            ui.hyperlink_to("View on GitHub", &rel.html_url);
+        }
+    });
+    if let Some(current) = &app.current_runtime {
+        ui.label(format!("Installed: {}", current.version));
+    }
+    ui.separator();
+    egui::ScrollArea::vertical().max_height(320.0).show(ui, |ui| {
+        if rel.body.trim().is_empty() {
+            ui.label("No release notes.");
+        } else {
+            ui.label(rel.body.trim());
+        }
+    });
+    ui.separator();
+    ui.horizontal(|ui| {
+        let available = app.runtime_update().is_some();
+        if ui
+            .add_enabled(
+                available,
+                crate::ui::light_button(
+                    &format!("⬇️ Install for {}", crate::runtime::backend_slug(be)),
+                    Color32::from_rgb(166, 227, 161),
+                ),
+            )
+            .clicked()
+        {
+            match crate::runtime::install_runtime(app, &rel, be) {
+                Ok(_) => {
+                    app.status = format!("Downloading runtime {}…", rel.tag_name);
+                    app.show_release_notes = false;
+                    app.show_downloads = true;
+                }
+                Err(e) => app.status = format!("Runtime err: {e}"),
+            }
+        }
+        if ui.add(crate::ui::light_button("Later", Color32::from_rgb(186, 194, 222))).clicked() {
+            app.update_dismissed = true;
+            app.show_release_notes = false;
+        }
+    });
+}
+
+/// Button pressed in the row above a settings editor
+enum EditorAction {
+    New,
+    Default,
+    Delete,
+}
+
+/// Picker for the item being edited plus New / Default / Delete buttons,
+/// shared by the preset, persona, connection and launch profile editors.
+/// With `unset_default` the Default button clears an optional default.
+fn editor_header(
+    ui: &mut egui::Ui,
+    label: &str,
+    names: &[String],
+    editing: &mut String,
+    is_default: bool,
+    unset_default: bool,
+    can_delete: bool,
+) -> Option<EditorAction> {
+    ui.horizontal(|ui| {
+        ui.label(label);
+        egui::ComboBox::from_id_source(("settings_editor", label))
+            .selected_text(editing.as_str())
+            .show_ui(ui, |ui| {
+                for n in names {
+                    ui.selectable_value(editing, n.clone(), n);
+                }
+            });
+    });
+    let mut action = None;
+    ui.horizontal(|ui| {
+        if ui.add(crate::ui::light_button("➕ New", Color32::from_rgb(166, 227, 161))).clicked() {
+            action = Some(EditorAction::New);
+        }
+        let (text, enabled) = if is_default && unset_default {
+            ("☆ Unset default", true)
+        } else {
+            ("⭐ Default", !is_default)
+        };
+        if ui.add_enabled(enabled, crate::ui::light_button(text, Color32::from_rgb(249, 226, 175))).clicked() {
+            action = Some(EditorAction::Default);
+        }
+        if ui.add_enabled(can_delete, crate::ui::light_button("🗑️ Delete", Color32::from_rgb(243, 139, 168))).clicked() {
+            action = Some(EditorAction::Delete);
+        }
+    });
+    action
+}
+
+/// `"{prefix} n"` with the first n, counting from the list length, that no
+/// item uses yet
+fn unique_name(prefix: &str, names: &[String]) -> String {
+    let mut n = names.len() + 1;
+    while names.iter().any(|x| *x == format!("{prefix} {n}")) {
+        n += 1;
+    }
+    format!("{prefix} {n}")
+}
+
+/// Name field that can be cleared and retyped: text that is empty or taken
+/// stays in `draft`, keyed by `kind` and the current name, instead of being
+/// applied. Returns the new name once the text is usable.
+fn name_field(ui: &mut egui::Ui, draft: &mut Option<(String, String)>, kind: &str, name: &str, taken: &[String]) -> Option<String> {
+    let key = format!("{kind}:{name}");
+    let mut text = match draft {
+        Some((k, t)) if *k == key => t.clone(),
+        _ => name.to_string(),
+    };
+    let mut renamed = None;
+    ui.horizontal(|ui| {
+        ui.label("Name:");
+        let edited = ui.text_edit_singleline(&mut text).changed();
+        let trimmed = text.trim();
+        let problem = if trimmed.is_empty() {
+            Some("Enter a name")
+        } else if taken.iter().any(|t| t == trimmed) {
+            Some("Name already in use")
+        } else {
+            None
+        };
+        match problem {
+            Some(p) => {
+                ui.colored_label(Color32::from_rgb(249, 226, 175), p);
+            }
+            None if edited && trimmed != name => renamed = Some(trimmed.to_string()),
+            None => {}
+        }
+    });
+    // The draft follows the item under its new name
+    let owner = renamed.as_deref().unwrap_or(name);
+    *draft = (text != owner).then(|| (format!("{kind}:{owner}"), text));
+    renamed
+}
+
+fn render_sampling_presets(app: &mut crate::app::App, ui: &mut egui::Ui) {
+    let mut changed = false;
+    let mut renamed: Option<(String, String)> = None;
+
+    let names: Vec<String> = app.settings.sampling_presets.iter().map(|p| p.name.clone()).collect();
+    let is_default = app.settings.default_preset == app.editing_preset;
+    let action = editor_header(ui, "Preset:", &names, &mut app.editing_preset, is_default, false, names.len() > 1);
+    match action {
+        Some(EditorAction::New) => {
+            let params = app
+                .settings
+                .sampling_presets
+                .iter()
+                .find(|p| p.name == app.editing_preset)
+                .map(|p| p.params.clone())
+                .unwrap_or_default();
+            let name = unique_name("Preset", &names);
+            app.settings.sampling_presets.push(SamplingPreset { name: name.clone(), params });
+            app.editing_preset = name;
+            changed = true;
+        }
+        Some(EditorAction::Default) => {
+            app.settings.default_preset = app.editing_preset.clone();
+            changed = true;
+        }
+        Some(EditorAction::Delete) => {
+            let name = app.editing_preset.clone();
+            app.settings.sampling_presets.retain(|p| p.name != name);
+            let first = app.settings.sampling_presets[0].name.clone();
+            if app.settings.default_preset == name {
+                app.settings.default_preset = first.clone();
+            }
+            // Chats and personas that used it fall back to the default
+            for persona in &mut app.settings.personas {
+                if persona.preset.as_deref() == Some(name.as_str()) {
+                    persona.preset = None;
+                }
+            }
+            app.remap_conversations(|c| &mut c.preset, &name, None);
+            if app.current_preset == name {
+                app.current_preset = app.settings.default_preset.clone();
+            }
+            app.editing_preset = first;
+            changed = true;
+        }
+        None => {}
+    }
+
+    let current = app.editing_preset.clone();
+    let taken: Vec<String> = app
+        .settings
+        .sampling_presets
+        .iter()
+        .filter(|p| p.name != current)
+        .map(|p| p.name.clone())
+        .collect();
+    let Some(preset) = app.settings.sampling_presets.iter_mut().find(|p| p.name == current) else {
+        ui.colored_label(Color32::from_rgb(249, 226, 175), "Preset not found");
+        return;
+    };
+
+    if let Some(name) = name_field(ui, &mut app.name_draft, "preset", &preset.name, &taken) {
+        if app.settings.default_preset == preset.name {
+            app.settings.default_preset = name.clone();
+        }
+        for persona in &mut app.settings.personas {
+            if persona.preset.as_ref() == Some(&preset.name) {
+                persona.preset = Some(name.clone());
+            }
+        }
+        if app.current_preset == preset.name {
+            app.current_preset = name.clone();
+        }
+        renamed = Some((preset.name.clone(), name.clone()));
+        preset.name = name.clone();
+        app.editing_preset = name;
+        changed = true;
+    }
+
+    let p = &mut preset.params;
+    egui::Grid::new("sampling_grid").num_columns(2).show(ui, |ui| {
+        ui.label("Temperature");
+        changed |= ui.add(egui::DragValue::new(&mut p.temperature).speed(0.01).range(0.0..=2.0)).changed();
+        ui.end_row();
+        ui.label("Max tokens");
+        changed |= ui.add(egui::DragValue::new(&mut p.max_tokens).range(-1..=32768)).changed();
+        ui.end_row();
+        ui.label("Top P");
+        changed |= ui.add(egui::DragValue::new(&mut p.top_p).speed(0.01).range(0.0..=1.0)).changed();
+        ui.end_row();
+        ui.label("Top K");
+        changed |= ui.add(egui::DragValue::new(&mut p.top_k).range(0..=200)).changed();
+        ui.end_row();
+        ui.label("Min P");
+        changed |= ui.add(egui::DragValue::new(&mut p.min_p).speed(0.01).range(0.0..=1.0)).changed();
+        ui.end_row();
+        ui.label("Repeat penalty");
+        changed |= ui.add(egui::DragValue::new(&mut p.repeat_penalty).speed(0.01).range(0.0..=2.0)).changed();
+        ui.end_row();
+        ui.label("Presence penalty");
+        changed |= ui.add(egui::DragValue::new(&mut p.presence_penalty).speed(0.01).range(-2.0..=2.0)).changed();
+        ui.end_row();
+        ui.label("Frequency penalty");
+        changed |= ui.add(egui::DragValue::new(&mut p.frequency_penalty).speed(0.01).range(-2.0..=2.0)).changed();
+        ui.end_row();
+        ui.label("Seed (-1 = random)");
+        changed |= ui.add(egui::DragValue::new(&mut p.seed).range(-1..=i64::from(u32::MAX))).changed();
+        ui.end_row();
+        ui.label("Mirostat");
+        ui.horizontal(|ui| {
+            for (v, label) in [(0u8, "Off"), (1, "v1"), (2, "v2")] {
+                changed |= ui.selectable_value(&mut p.mirostat, v, label).changed();
+            }
+        });
+        ui.end_row();
+        if p.mirostat > 0 {
+            ui.label("Mirostat tau");
+            changed |= ui.add(egui::DragValue::new(&mut p.mirostat_tau).speed(0.1).range(0.0..=10.0)).changed();
+            ui.end_row();
+            ui.label("Mirostat eta");
+            changed |= ui.add(egui::DragValue::new(&mut p.mirostat_eta).speed(0.01).range(0.0..=1.0)).changed();
+            ui.end_row();
+        }
+    });
+
+    ui.label("Stop sequences (one per line, \\n for newline):");
+    let mut stops = p.stop.join("\n");
+    if ui.text_edit_multiline(&mut stops).changed() {
+        p.stop = stops.split('\n').map(String::from).collect();
+        changed = true;
+    }
+
+    if let Some((old, new)) = renamed {
+        app.remap_conversations(|c| &mut c.preset, &old, Some(&new));
+    }
+    if changed {
+        let _ = app.save_settings();
+    }
+}
+
+fn render_personas(app: &mut crate::app::App, ui: &mut egui::Ui) {
+    let mut changed = false;
+    let mut renamed: Option<(String, String)> = None;
+
+    let names: Vec<String> = app.settings.personas.iter().map(|p| p.name.clone()).collect();
+    if !names.contains(&app.editing_persona) {
+        app.editing_persona = names.first().cloned().unwrap_or_default();
+    }
+    let is_default = app.settings.default_persona.as_ref() == Some(&app.editing_persona);
+    let action = editor_header(ui, "Persona:", &names, &mut app.editing_persona, is_default, true, !names.is_empty());
+    match action {
+        Some(EditorAction::New) => {
+            let name = unique_name("Persona", &names);
+            app.settings.personas.push(Persona {
+                name: name.clone(),
+                system_prompt: String::new(),
+                preset: None,
+            });
+            app.editing_persona = name;
+            changed = true;
+        }
+        Some(EditorAction::Default) => {
+            app.settings.default_persona = if is_default { None } else { Some(app.editing_persona.clone()) };
+            changed = true;
+        }
+        Some(EditorAction::Delete) => {
+            let name = app.editing_persona.clone();
+            app.settings.personas.retain(|p| p.name != name);
+            if app.settings.default_persona.as_ref() == Some(&name) {
+                app.settings.default_persona = None;
+            }
+            if app.current_persona.as_ref() == Some(&name) {
+                app.current_persona = None;
+            }
+            app.remap_conversations(|c| &mut c.persona, &name, None);
+            app.editing_persona = app.settings.personas.first().map(|p| p.name.clone()).unwrap_or_default();
+            changed = true;
+        }
+        None => {}
+    }
+
+    let current = app.editing_persona.clone();
+    let taken: Vec<String> = names.iter().filter(|n| **n != current).cloned().collect();
+    let preset_names: Vec<String> = app.settings.sampling_presets.iter().map(|p| p.name.clone()).collect();
+    let Some(persona) = app.settings.personas.iter_mut().find(|p| p.name == current) else {
+        ui.label(RichText::new("No personas yet.").small().color(Color32::from_rgb(186, 194, 222)));
+        if changed {
+            let _ = app.save_settings();
+        }
+        return;
+    };
+
+    if let Some(name) = name_field(ui, &mut app.name_draft, "persona", &persona.name, &taken) {
+        if app.settings.default_persona.as_ref() == Some(&persona.name) {
+            app.settings.default_persona = Some(name.clone());
+        }
+        if app.current_persona.as_ref() == Some(&persona.name) {
+            app.current_persona = Some(name.clone());
+        }
+        renamed = Some((persona.name.clone(), name.clone()));
+        persona.name = name.clone();
+        app.editing_persona = name;
+        changed = true;
+    }
+    ui.label("System prompt:");
+    changed |= ui.text_edit_multiline(&mut persona.system_prompt).changed();
+    ui.horizontal(|ui| {
+        ui.label("Preset:");
+        egui::ComboBox::from_id_source("persona_preset")
+            .selected_text(persona.preset.as_deref().unwrap_or("(keep current)"))
+            .show_ui(ui, |ui| {
+                changed |= ui.selectable_value(&mut persona.preset, None, "(keep current)").changed();
+                for n in &preset_names {
+                    changed |= ui.selectable_value(&mut persona.preset, Some(n.clone()), n).changed();
+                }
+            });
+    });
+
+    if let Some((old, new)) = renamed {
+        app.remap_conversations(|c| &mut c.persona, &old, Some(&new));
+    }
+    if changed {
+        let _ = app.save_settings();
+    }
+}
+
+fn render_connections(app: &mut crate::app::App, ui: &mut egui::Ui) {
+    let mut changed = false;
+    let mut renamed: Option<(String, String)> = None;
+
+    let names: Vec<String> = app.settings.connections.iter().map(|c| c.name.clone()).collect();
+    let is_default = app.settings.default_connection == app.editing_connection;
+    let is_local = app
+        .settings
+        .connections
+        .iter()
+        .any(|c| c.name == app.editing_connection && c.kind == ConnectionKind::LocalManaged);
+    let action = editor_header(ui, "Connection:", &names, &mut app.editing_connection, is_default, false, !is_local);
+    match action {
+        Some(EditorAction::New) => {
+            let name = unique_name("Connection", &names);
+            app.settings.connections.push(Connection {
+                name: name.clone(),
+                kind: ConnectionKind::LlamaServer,
+                url: ConnectionKind::LlamaServer.default_url().to_string(),
+                ..Default::default()
+            });
+            app.editing_connection = name;
+            changed = true;
+        }
+        Some(EditorAction::Default) => {
+            app.settings.default_connection = app.editing_connection.clone();
+            changed = true;
+        }
+        Some(EditorAction::Delete) => {
+            let name = app.editing_connection.clone();
+            app.settings.connections.retain(|c| c.name != name);
+            app.connection_health.remove(&name);
+            let first = app.settings.connections.first().map(|c| c.name.clone()).unwrap_or_default();
+            if app.settings.default_connection == name {
+                app.settings.default_connection = first.clone();
+            }
+            if app.current_connection == name {
+                app.current_connection = app.settings.default_connection.clone();
+            }
+            // Chats that used it go to the default connection when opened
+            app.remap_conversations(|c| &mut c.connection, &name, None);
+            app.editing_connection = first;
+            changed = true;
+        }
+        None => {}
+    }
+
+    let current = app.editing_connection.clone();
+    ui.horizontal(|ui| {
+        if ui.add(crate::ui::light_button("🩺 Check", Color32::from_rgb(137, 180, 250))).clicked() {
+            app.check_connection(&current);
+        }
+        crate::ui::connection_health_label(ui, app.connection_health.get(&current));
+    });
+    let taken: Vec<String> = app
+        .settings
+        .connections
+        .iter()
+        .filter(|c| c.name != current)
+        .map(|c| c.name.clone())
+        .collect();
+    let Some(conn) = app.settings.connections.iter_mut().find(|c| c.name == current) else {
+        ui.colored_label(Color32::from_rgb(249, 226, 175), "Connection not found");
+        return;
+    };
+
+    if let Some(name) = name_field(ui, &mut app.name_draft, "connection", &conn.name, &taken) {
+        if app.settings.default_connection == conn.name {
+            app.settings.default_connection = name.clone();
+        }
+        if app.current_connection == conn.name {
+            app.current_connection = name.clone();
+        }
+        if let Some(h) = app.connection_health.remove(&conn.name) {
+            app.connection_health.insert(name.clone(), h);
+        }
+        renamed = Some((conn.name.clone(), name.clone()));
+        conn.name = name.clone();
+        app.editing_connection = name;
+        changed = true;
+    }
+
+    if conn.kind == ConnectionKind::LocalManaged {
+        ui.label(RichText::new("Chats go to the llama-server started by this app.").small().color(Color32::from_rgb(186, 194, 222)));
+    } else {
+        egui::Grid::new("connection_grid").num_columns(2).show(ui, |ui| {
+            ui.label("Type");
+            let before = conn.kind;
+            egui::ComboBox::from_id_source("connection_kind")
+                .selected_text(conn.kind.label())
+                .show_ui(ui, |ui| {
+                    for k in ConnectionKind::ALL.into_iter().filter(|k| *k != ConnectionKind::LocalManaged) {
+                        ui.selectable_value(&mut conn.kind, k, k.label());
+                    }
+                });
+            if conn.kind != before {
+                // Follow the new server type unless the URL was customised
+                if conn.url.is_empty() || conn.url == before.default_url() {
+                    conn.url = conn.kind.default_url().to_string();
+                }
+                changed = true;
+            }
+            ui.end_row();
+            ui.label("URL");
+            let mut endpoint_changed = ui.text_edit_singleline(&mut conn.url).changed();
+            ui.end_row();
+            ui.label("API key");
+            endpoint_changed |= ui
+                .add(egui::TextEdit::singleline(&mut conn.api_key).password(true))
+                .on_hover_text("Stored in plain text in settings.json")
+                .changed();
+            ui.end_row();
+            if !conn.api_key.is_empty() {
+                ui.label("");
+                ui.label(
+                    RichText::new("⚠ The key is saved unencrypted in settings.json")
+                        .small()
+                        .color(Color32::from_rgb(249, 226, 175)),
+                );
+                ui.end_row();
+            }
+            if endpoint_changed {
+                app.connection_health.remove(&conn.name);
+                changed = true;
+            }
+            ui.label("Model");
+            changed |= ui
+                .add(egui::TextEdit::singleline(&mut conn.model).hint_text("server default"))
+                .changed();
+            ui.end_row();
+        });
+    }
+
+    if let Some((old, new)) = renamed {
+        app.remap_conversations(|c| &mut c.connection, &old, Some(&new));
+    }
+    if changed {
+        let _ = app.save_settings();
+    }
+}
+
+fn render_launch_profiles(app: &mut crate::app::App, ui: &mut egui::Ui) {
+    let mut changed = false;
+
+    let names: Vec<String> = app.settings.launch_profiles.iter().map(|p| p.name.clone()).collect();
+    let is_default = app.settings.default_launch_profile == app.editing_profile;
+    let action = editor_header(ui, "Profile:", &names, &mut app.editing_profile, is_default, false, names.len() > 1);
+    match action {
+        Some(EditorAction::New) => {
+            let base = app
+                .settings
+                .launch_profiles
+                .iter()
+                .find(|p| p.name == app.editing_profile)
+                .cloned()
+                .unwrap_or_default();
+            let name = unique_name("Profile", &names);
+            app.settings.launch_profiles.push(LaunchProfile { name: name.clone(), ..base });
+            app.editing_profile = name;
+            changed = true;
+        }
+        Some(EditorAction::Default) => {
+            app.settings.default_launch_profile = app.editing_profile.clone();
+            changed = true;
+        }
+        Some(EditorAction::Delete) => {
+            let name = app.editing_profile.clone();
+            app.settings.launch_profiles.retain(|p| p.name != name);
+            app.settings.model_profiles.retain(|_, p| *p != name);
+            let first = app.settings.launch_profiles[0].name.clone();
+            if app.settings.default_launch_profile == name {
+                app.settings.default_launch_profile = first.clone();
+            }
+            app.editing_profile = first;
+            changed = true;
+        }
+        None => {}
+    }
+
+    let current = app.editing_profile.clone();
+    let taken: Vec<String> = app
+        .settings
+        .launch_profiles
+        .iter()
+        .filter(|p| p.name != current)
+        .map(|p| p.name.clone())
+        .collect();
+    let Some(profile) = app.settings.launch_profiles.iter_mut().find(|p| p.name == current) else {
+        ui.colored_label(Color32::from_rgb(249, 226, 175), "Profile not found");
+        return;
+    };
+
+    if let Some(name) = name_field(ui, &mut app.name_draft, "profile", &profile.name, &taken) {
+        if app.settings.default_launch_profile == profile.name {
+            app.settings.default_launch_profile = name.clone();
+        }
+        for p in app.settings.model_profiles.values_mut() {
+            if *p == profile.name {
+                *p = name.clone();
+            }
+        }
+        profile.name = name.clone();
+        app.editing_profile = name;
+        changed = true;
+    }
+
+    egui::Grid::new("launch_profile_grid").num_columns(2).show(ui, |ui| {
+        ui.label("Context size (0 = model)");
+        changed |= ui.add(egui::DragValue::new(&mut profile.ctx_size).speed(256).range(0..=1_048_576)).changed();
+        ui.end_row();
+        ui.label("Threads (-1 = auto)");
+        changed |= ui.add(egui::DragValue::new(&mut profile.threads).range(-1..=256)).changed();
+        ui.end_row();
+        ui.label("Batch size");
+        changed |= ui.add(egui::DragValue::new(&mut profile.batch_size).speed(64).range(0..=65536)).changed();
+        ui.end_row();
+        ui.label("GPU layers (-1 = auto)");
+        changed |= ui.add(egui::DragValue::new(&mut profile.gpu_layers).range(-1..=999)).changed();
+        ui.end_row();
+        ui.label("Parallel slots");
+        changed |= ui.add(egui::DragValue::new(&mut profile.parallel).range(1..=64)).changed();
+        ui.end_row();
+        for (label, value, id) in [
+            ("KV cache K", &mut profile.cache_type_k, "cache_k"),
+            ("KV cache V", &mut profile.cache_type_v, "cache_v"),
+        ] {
+            ui.label(label);
+            egui::ComboBox::from_id_source(id)
+                .selected_text(value.as_str())
+                .show_ui(ui, |ui| {
+                    for t in KV_CACHE_TYPES {
+                        changed |= ui.selectable_value(value, t.to_string(), *t).changed();
+                    }
+                });
+            ui.end_row();
+        }
+    });
+    changed |= ui.checkbox(&mut profile.flash_attn, "Flash attention").changed();
+    changed |= ui.checkbox(&mut profile.mlock, "Lock model in RAM (mlock)").changed();
+    changed |= ui.checkbox(&mut profile.mmap, "Memory-map model (mmap)").changed();
+    ui.label("Extra arguments:");
+    changed |= ui.text_edit_singleline(&mut profile.extra_args).changed();
+
+    ui.label(
+        RichText::new(format!(
+            "llama-server {}",
+            crate::server::launch_args(profile, app.backend).join(" ")
+        ))
+        .small()
+        .color(Color32::from_rgb(186, 194, 222)),
+    );
+
+    if changed {
+        let _ = app.save_settings();
+    }
+}
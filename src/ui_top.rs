@@ -1,7 +1,7 @@
 use crate::models::{Backend, HFFile};
 use crate::download::human_size;
 use eframe::egui::{self, Color32, RichText};
-use std::{fs, process::Command, path::PathBuf};
+use std::{fs, path::Path, process::Command, path::PathBuf};
 
 pub fn render_top_panel(app: &mut crate::app::App, ui: &mut egui::Ui) {
     ui.horizontal(|ui| {
@@ -24,17 +24,10 @@ pub fn render_top_panel(app: &mut crate::app::App, ui: &mut egui::Ui) {
         ui.label(RichText::new("📄 File:").color(Color32::from_rgb(137, 180, 250)));
         ui.text_edit_singleline(&mut app.model_file);
 
-        if ui.add(crate::ui::light_button("⬇️ Download model", Color32::from_rgb(166, 227, 161))).clicked() {
-            let _ = crate::runtime::start_model_download(app);
-        }
-
-        if let Some((cur, tot, stage)) = &app.model_progress {
-            let frac = tot.map(|t| *cur as f32 / t as f32).unwrap_or(0.0);
-            ui.add(
-                egui::ProgressBar::new(frac)
-                    .text(format!("{stage} {}", human_size(*cur)))
-                    .fill(Color32::from_rgb(249, 226, 175)),
-            );
+        if ui.add(crate::ui::light_button("⬇️ Download model", Color32::from_rgb(166, 227, 161))).clicked()
+            && let Err(e) = crate::runtime::start_model_download(app)
+        {
+            app.status = format!("Model err: {e}");
         }
     });
 
@@ -72,6 +65,7 @@ pub fn render_top_panel(app: &mut crate::app::App, ui: &mut egui::Ui) {
                                 Ok(files) => {
                                     app.selected_model = Some(id.clone());
                                     app.files_for_selected = files;
+                                    app.hf_headers.clear();
                                 }
                                 Err(e) => {
                                     app.search_status = format!("Files err: {e}");
@@ -85,22 +79,51 @@ pub fn render_top_panel(app: &mut crate::app::App, ui: &mut egui::Ui) {
 
     ui.separator();
 
-    crate::ui_models::render_downloaded_models(app, ui);
-
     if let Some(model_id) = app.selected_model.clone() {
-        let files = app.files_for_selected.clone();
+        let groups = crate::shards::group_hf_files(&app.files_for_selected);
         ui.collapsing(format!("📄 {} files", model_id), |ui| {
             egui::ScrollArea::vertical()
                 .max_height(160.0)
                 .show(ui, |ui| {
-                    for f in files.clone() {
-                        let size_txt = f.size.map(human_size).unwrap_or("?".into());
+                    for g in groups {
+                        let size_txt = g.size().map(human_size).unwrap_or("?".into());
                         ui.horizontal(|ui| {
-                            ui.label(format!("{} ({})", f.rfilename, size_txt));
+                            if g.files.len() > 1 {
+                                ui.label(format!("{} ({}, {} parts)", g.name, size_txt, g.files.len()));
+                            } else {
+                                ui.label(format!("{} ({})", g.name, size_txt));
+                            }
                             if ui.add(crate::ui::light_button("⬇️ Download", Color32::from_rgb(166, 227, 161))).clicked() {
                                 app.model_repo = model_id.clone();
-                                app.model_file = f.rfilename.clone();
-                                let _ = crate::runtime::start_model_download(app);
+                                app.model_file = g.files[0].rfilename.clone();
+                                if let Err(e) = crate::runtime::start_model_download(app) {
+                                    app.status = format!("Model err: {e}");
+                                }
+                            }
+                            let first = &g.files[0].rfilename;
+                            match app.hf_headers.get(first) {
+                                Some(Some(Ok(info))) => {
+                                    let profile = app.launch_profile_for(Path::new(first));
+                                    let est = app.estimate_fit(info, g.size().unwrap_or(0), &profile);
+                                    crate::ui::fit_label(ui, &est);
+                                }
+                                Some(Some(Err(e))) => {
+                                    ui.colored_label(Color32::from_rgb(243, 139, 168), "⚠ no estimate")
+                                        .on_hover_text(e);
+                                }
+                                Some(None) => {
+                                    ui.spinner();
+                                    ui.label("Checking…");
+                                }
+                                None => {
+                                    if ui
+                                        .add(crate::ui::light_button("📏 Will it fit?", Color32::from_rgb(249, 226, 175)))
+                                        .on_hover_text("Read the GGUF header to estimate memory use")
+                                        .clicked()
+                                    {
+                                        app.fetch_hf_header(&model_id, first);
+                                    }
+                                }
                             }
                         });
                     }
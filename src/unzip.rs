@@ -32,6 +32,7 @@ pub fn unzip(file: &PathBuf, dst: &PathBuf) -> anyhow::Result<()> {
 pub fn unzip_with_progress(
     zip_file: &PathBuf,
     dst: &PathBuf,
+    job: crate::models::JobId,
     tx: &mpsc::Sender<crate::models::DownloadEvent>,
 ) -> anyhow::Result<()> {
     let f = fs::File::open(zip_file)?;
@@ -55,6 +56,7 @@ pub fn unzip_with_progress(
             }
         }
         let _ = tx.send(crate::models::DownloadEvent::Progress {
+            job,
             kind: crate::models::DownloadKind::Runtime,
             current: (i as u64) + 1,
             total: Some(total),